@@ -0,0 +1,514 @@
+use crate::commands::pwm::{
+    edit::{EntryChanges, PasswordChange},
+    printing::PrintingMode,
+};
+use crate::locker::{CipherSuite, KdfOptions, KdfParams};
+use crate::passwords::{
+    PasswordFilter, PasswordGeneratorOptions, SortBy, DEFAULT_PASSWORD_LENGTH,
+};
+use clap::{Args, Parser, Subcommand};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[derive(Parser)]
+#[command(name = "pwm", version, about = "A password manager and file locker")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Encrypt a file in place with a password
+    Lock {
+        path: PathBuf,
+        /// Set the immutable flag on the file after locking it
+        #[arg(short, long)]
+        immutable: bool,
+        /// The cipher suite to encrypt the file with, "xchacha20-poly1305" or "aes-256-gcm-siv".
+        /// The one in the config file if not given, which defaults to xchacha20-poly1305
+        #[arg(long, value_name = "SUITE")]
+        cipher: Option<CipherSuite>,
+        #[command(flatten)]
+        kdf: KdfArgs,
+    },
+    /// Decrypt a file that was locked with `pwm lock`
+    Unlock {
+        path: PathBuf,
+        /// The key file that the file requires along with the password
+        #[arg(long, value_name = "PATH")]
+        keyfile: Option<PathBuf>,
+    },
+    /// Convert a file written by an older version of pwm to the current format
+    Upgrade {
+        /// The file to convert, the passwords file if not given
+        path: Option<PathBuf>,
+        /// Also convert the entries of the file, for copies of the passwords file such as
+        /// backups. The entries of the passwords file itself are always converted
+        #[arg(short, long)]
+        entries: bool,
+    },
+    /// Verify that a locked file can be unlocked and is intact, exiting with a non-zero status
+    /// otherwise
+    Check {
+        /// The file to check, the passwords file if not given
+        path: Option<PathBuf>,
+        /// Also check the entries of the file, for copies of the passwords file such as backups.
+        /// The entries of the passwords file itself are always checked
+        #[arg(short, long)]
+        entries: bool,
+    },
+    /// Print the entries of the passwords file that match the given filters
+    Get {
+        #[command(flatten)]
+        filter: FilterArgs,
+        /// Group the entries by "domain", "username" or the name of an additional field
+        #[arg(short, long, value_name = "FIELD")]
+        sort_by: Option<SortBy>,
+        /// The key file that the passwords file requires along with the master password
+        #[arg(long, value_name = "PATH")]
+        keyfile: Option<PathBuf>,
+        /// Print the additional fields of every entry
+        #[arg(short, long)]
+        verbose: bool,
+    },
+    /// Add a new entry to the passwords file
+    New {
+        #[arg(short, long)]
+        username: String,
+        #[arg(short, long)]
+        domain: String,
+        /// An additional field to store with the entry, may be repeated
+        #[arg(short, long = "field", value_name = "NAME=VALUE", value_parser = parse_field)]
+        fields: Vec<(String, String)>,
+        /// Prompt for the password instead of generating one
+        #[arg(short, long)]
+        password: bool,
+        #[command(flatten)]
+        generator: GeneratorArgs,
+        /// The key file that the passwords file requires along with the master password
+        #[arg(long, value_name = "PATH")]
+        keyfile: Option<PathBuf>,
+    },
+    /// Change the fields of the single entry that matches the given filters
+    Edit {
+        #[command(flatten)]
+        filter: FilterArgs,
+        #[command(flatten)]
+        changes: ChangesArgs,
+    },
+    /// Change the master password of the passwords file
+    Passwd {
+        #[command(flatten)]
+        kdf: KdfArgs,
+    },
+    /// Find key derivation parameters that make unlocking take about the given time
+    Calibrate {
+        /// The target unlock time in milliseconds
+        #[arg(long, value_name = "MS", default_value_t = 1000)]
+        target_time: u64,
+        #[command(flatten)]
+        kdf: KdfCostArgs,
+    },
+    /// Move the entries that match the given filters to the trash
+    Remove {
+        #[command(flatten)]
+        filter: FilterArgs,
+        /// Don't ask for confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Manage the entries that were removed
+    Trash {
+        #[command(subcommand)]
+        command: TrashCommand,
+    },
+    /// Write the entries that can still be read from a corrupted passwords file to a new one,
+    /// which is locked with the same master password
+    Salvage {
+        /// Where to write the new passwords file, must not exist
+        output: PathBuf,
+        /// The file to recover the entries of, the passwords file if not given
+        #[arg(long, value_name = "PATH")]
+        from: Option<PathBuf>,
+        /// Decrypt the file even if it fails authentication, in which case its content might have
+        /// been tampered with
+        #[arg(long)]
+        ignore_authentication: bool,
+    },
+    /// Split the key of the passwords file into shares, any threshold of which unlock it
+    Split {
+        /// The amount of shares to split the key into
+        #[arg(short = 'n', long, value_parser = clap::value_parser!(u8).range(2..))]
+        shares: u8,
+        /// The amount of shares that are needed to unlock the passwords file
+        #[arg(short = 'k', long, value_parser = clap::value_parser!(u8).range(2..))]
+        threshold: u8,
+    },
+    /// Unlock the passwords file with shares of its key, and set a new master password for it
+    Recover {
+        #[command(flatten)]
+        kdf: KdfArgs,
+    },
+    /// Manage the key slots of a file, each of which unlocks it with its own password
+    Slot {
+        #[command(subcommand)]
+        command: SlotCommand,
+    },
+    /// Require a key file along with the password that unlocks a file, or stop requiring one
+    Keyfile {
+        #[command(subcommand)]
+        command: KeyfileCommand,
+    },
+    /// Manage the decoy vault of the passwords file, a hidden set of entries that a duress password
+    /// opens, whose existence can't be proven
+    Decoy {
+        #[command(subcommand)]
+        command: DecoyCommand,
+    },
+    /// Manage your identity, a key pair whose public key others encrypt files and entries to
+    Identity {
+        #[command(subcommand)]
+        command: IdentityCommand,
+    },
+    /// Manage the recipients of a file, whose identities unlock it without a password
+    Recipients {
+        #[command(subcommand)]
+        command: RecipientsCommand,
+    },
+    /// Write the single entry that matches the given filters to a file that only the owner of the
+    /// given public key can receive
+    Share {
+        /// The public key of the recipient, as printed by `pwm identity show`
+        public_key: String,
+        /// Where to write the shared entry, must not exist
+        output: PathBuf,
+        #[command(flatten)]
+        filter: FilterArgs,
+    },
+    /// Add an entry that was shared with your identity to the passwords file
+    Receive {
+        /// The file that was written by `pwm share`
+        path: PathBuf,
+    },
+    /// Manage the backups of the passwords file, which are made before each time it's changed
+    Backup {
+        #[command(subcommand)]
+        command: BackupCommand,
+    },
+    /// Print a completion script for the given shell
+    Completions { shell: clap_complete::Shell },
+}
+
+#[derive(Subcommand)]
+pub enum TrashCommand {
+    /// List the entries in the trash
+    List,
+    /// Move the entries in the trash that match the given filters back to the passwords file
+    Restore {
+        #[command(flatten)]
+        filter: FilterArgs,
+        /// Don't ask for confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Permanently delete the entries in the trash that match the given filters
+    Purge {
+        #[command(flatten)]
+        filter: FilterArgs,
+        /// Don't ask for confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum SlotCommand {
+    /// List the key slots of the file, which doesn't require unlocking it
+    List {
+        /// The file to list the key slots of, the passwords file if not given
+        path: Option<PathBuf>,
+    },
+    /// Add a key slot that unlocks the file with a new password, without re-encrypting it
+    Add {
+        /// The file to add the key slot to, the passwords file if not given
+        path: Option<PathBuf>,
+        /// A name that tells the key slot apart from the others, "recovery" for recovery codes
+        /// and "password" otherwise if not given
+        #[arg(short, long)]
+        label: Option<String>,
+        /// Generate a recovery code as the password of the key slot instead of prompting for one
+        #[arg(short, long)]
+        recovery: bool,
+        /// Also require this key file to unlock the file with the new key slot
+        #[arg(long, value_name = "PATH")]
+        slot_keyfile: Option<PathBuf>,
+        /// The key file that the key slot of the entered password requires
+        #[arg(long, value_name = "PATH")]
+        keyfile: Option<PathBuf>,
+        #[command(flatten)]
+        kdf: KdfArgs,
+    },
+    /// Revoke the key slot with the given id, so that its password no longer unlocks the file
+    Revoke {
+        id: usize,
+        /// The file to revoke the key slot of, the passwords file if not given
+        path: Option<PathBuf>,
+        /// The key file that the key slot of the entered password requires
+        #[arg(long, value_name = "PATH")]
+        keyfile: Option<PathBuf>,
+        /// Don't ask for confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum KeyfileCommand {
+    /// Require the given key file along with the password that is entered to unlock the file
+    Add {
+        /// The key file, which can be any file whose content never changes
+        keyfile: PathBuf,
+        /// The file that will require the key file, the passwords file if not given
+        path: Option<PathBuf>,
+        /// Generate a new key file of 32 random bytes at the given path instead of using an
+        /// existing file
+        #[arg(short, long)]
+        generate: bool,
+    },
+    /// Stop requiring a key file along with the password that is entered to unlock the file
+    Remove {
+        /// The file to stop requiring a key file for, the passwords file if not given
+        path: Option<PathBuf>,
+        /// The key file that the file currently requires, the one in the config file if not given
+        #[arg(long, value_name = "PATH")]
+        keyfile: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum DecoyCommand {
+    /// Create an empty decoy vault, which the other commands use when they're given the duress
+    /// password instead of the master password
+    Create {
+        /// Don't ask for confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Destroy the decoy vault by filling the space it takes with random bytes
+    Remove {
+        /// Don't ask for confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum IdentityCommand {
+    /// Create your identity in the config directory, or at the path set in the config file
+    New,
+    /// Print the public key of your identity, which others encrypt to
+    Show,
+}
+
+#[derive(Subcommand)]
+pub enum RecipientsCommand {
+    /// Encrypt the file to a recipient, whose identity then unlocks it without a password
+    Add {
+        /// The public key of the recipient, as printed by `pwm identity show`
+        public_key: String,
+        /// The file to add the recipient to, the passwords file if not given
+        path: Option<PathBuf>,
+        /// A name that tells the recipient apart from the others, "recipient" if not given
+        #[arg(short, long)]
+        label: Option<String>,
+        /// The key file that the key slot of the entered password requires
+        #[arg(long, value_name = "PATH")]
+        keyfile: Option<PathBuf>,
+    },
+    /// Remove a recipient and re-encrypt the file with a new key, which also revokes the key
+    /// slots of the passwords that weren't entered
+    Remove {
+        /// The public key of the recipient
+        public_key: String,
+        /// The file to remove the recipient from, the passwords file if not given
+        path: Option<PathBuf>,
+        /// The key file that the key slot of the entered password requires
+        #[arg(long, value_name = "PATH")]
+        keyfile: Option<PathBuf>,
+        /// Don't ask for confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum BackupCommand {
+    /// List the backups of the passwords file, from the newest to the oldest
+    List,
+    /// Replace the passwords file with the backup with the given id
+    Restore {
+        id: u64,
+        /// Don't ask for confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
+}
+
+#[derive(Args)]
+pub struct FilterArgs {
+    /// Only match entries whose domain contains this text
+    #[arg(value_name = "DOMAIN")]
+    domain: Option<String>,
+    /// Only match entries whose username contains this text
+    #[arg(short, long)]
+    username: Option<String>,
+    /// Only match entries whose password contains this text
+    #[arg(short, long)]
+    password: Option<String>,
+    /// Only match entries with an additional field containing VALUE, may be repeated
+    #[arg(short, long = "field", value_name = "NAME=VALUE", value_parser = parse_field)]
+    fields: Vec<(String, String)>,
+}
+impl From<FilterArgs> for PasswordFilter {
+    fn from(args: FilterArgs) -> PasswordFilter {
+        PasswordFilter::new(
+            args.password,
+            args.domain,
+            args.username,
+            fields_map(args.fields),
+        )
+    }
+}
+
+#[derive(Args)]
+pub struct ChangesArgs {
+    /// Prompt for a new password
+    #[arg(long, conflicts_with = "generate_password")]
+    set_password: bool,
+    /// Replace the password with a newly generated one
+    #[arg(long)]
+    generate_password: bool,
+    #[arg(long, value_name = "USERNAME")]
+    set_username: Option<String>,
+    #[arg(long, value_name = "DOMAIN")]
+    set_domain: Option<String>,
+    /// Add an additional field or change its value, may be repeated
+    #[arg(long = "set-field", value_name = "NAME=VALUE", value_parser = parse_field)]
+    set_fields: Vec<(String, String)>,
+    /// Remove an additional field, may be repeated
+    #[arg(long = "remove-field", value_name = "NAME")]
+    remove_fields: Vec<String>,
+    /// Edit the value of an additional field in $EDITOR, may be repeated
+    #[arg(long = "edit-field", value_name = "NAME")]
+    edit_fields: Vec<String>,
+    #[command(flatten)]
+    generator: GeneratorArgs,
+}
+impl From<ChangesArgs> for EntryChanges {
+    fn from(args: ChangesArgs) -> EntryChanges {
+        EntryChanges {
+            password: if args.set_password {
+                PasswordChange::Prompt
+            } else if args.generate_password {
+                PasswordChange::Generate(args.generator.into())
+            } else {
+                PasswordChange::Keep
+            },
+            username: args.set_username,
+            domain: args.set_domain,
+            set_fields: fields_map(args.set_fields),
+            remove_fields: args.remove_fields,
+            edit_fields: args.edit_fields,
+        }
+    }
+}
+
+// the cost of each iteration of the key derivation, which calibration keeps while it picks the
+// amount of iterations
+#[derive(Args)]
+pub struct KdfCostArgs {
+    /// The amount of memory used to derive the key from the password, in MiB
+    #[arg(long, value_name = "MIB", default_value_t = KdfParams::DEFAULT.memory_kib / 1024, value_parser = clap::value_parser!(u32).range(1..=4096))]
+    kdf_memory: u32,
+    /// The amount of lanes used to derive the key from the password
+    #[arg(long, value_name = "N", default_value_t = KdfParams::DEFAULT.parallelism, value_parser = clap::value_parser!(u32).range(1..=16))]
+    kdf_parallelism: u32,
+}
+impl KdfCostArgs {
+    pub fn options(self, iterations: Option<u32>, target_time: Option<u64>) -> KdfOptions {
+        KdfOptions {
+            memory_kib: self.kdf_memory * 1024,
+            iterations,
+            parallelism: self.kdf_parallelism,
+            target_time: target_time.map(Duration::from_millis),
+        }
+    }
+}
+
+#[derive(Args)]
+pub struct KdfArgs {
+    #[command(flatten)]
+    cost: KdfCostArgs,
+    /// The amount of iterations used to derive the key from the password
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..=1000))]
+    kdf_iterations: Option<u32>,
+    /// Pick the amount of iterations so that unlocking takes about this many milliseconds
+    #[arg(long, value_name = "MS", conflicts_with = "kdf_iterations")]
+    kdf_target_time: Option<u64>,
+}
+impl KdfArgs {
+    pub fn options(self) -> KdfOptions {
+        self.cost.options(self.kdf_iterations, self.kdf_target_time)
+    }
+}
+
+#[derive(Args)]
+pub struct GeneratorArgs {
+    /// The length of the generated password
+    #[arg(short, long, default_value_t = DEFAULT_PASSWORD_LENGTH)]
+    length: usize,
+    /// Don't use lowercase letters in the generated password
+    #[arg(long)]
+    no_lowercase: bool,
+    /// Don't use uppercase letters in the generated password
+    #[arg(long)]
+    no_uppercase: bool,
+    /// Don't use digits in the generated password
+    #[arg(long)]
+    no_digits: bool,
+    /// Don't use symbols in the generated password
+    #[arg(long)]
+    no_symbols: bool,
+}
+impl From<GeneratorArgs> for PasswordGeneratorOptions {
+    fn from(args: GeneratorArgs) -> PasswordGeneratorOptions {
+        let mut options = PasswordGeneratorOptions::new();
+        options.password_length = args.length;
+        options.use_lowercase = !args.no_lowercase;
+        options.use_uppercase = !args.no_uppercase;
+        options.use_digits = !args.no_digits;
+        options.use_symbols = !args.no_symbols;
+        options
+    }
+}
+
+pub fn printing_mode(verbose: bool) -> PrintingMode {
+    if verbose {
+        PrintingMode::Verbose
+    } else {
+        PrintingMode::Normal
+    }
+}
+
+pub fn fields_map(fields: Vec<(String, String)>) -> HashMap<String, String> {
+    fields.into_iter().collect()
+}
+
+fn parse_field(s: &str) -> std::result::Result<(String, String), String> {
+    match s.find('=') {
+        Some(index) => Ok((s[..index].to_string(), s[index + 1..].to_string())),
+        None => Err(format!("invalid field `{}`, expected NAME=VALUE", s)),
+    }
+}
@@ -0,0 +1,25 @@
+use crate::locker::{print_error, KdfOptions};
+use crate::styles::{error_style, success_style};
+use std::process::ExitCode;
+
+pub fn calibrate_command(kdf_options: KdfOptions) -> ExitCode {
+    println!("Calibrating...");
+    match kdf_options.params() {
+        Ok(kdf_params) => {
+            println!(
+                "{}",
+                success_style().paint(format!(
+                    "--kdf-memory {} --kdf-iterations {} --kdf-parallelism {}",
+                    kdf_params.memory_kib / 1024,
+                    kdf_params.iterations,
+                    kdf_params.parallelism
+                ))
+            );
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            print_error(e, "target", &error_style());
+            ExitCode::FAILURE
+        }
+    }
+}
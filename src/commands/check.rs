@@ -0,0 +1,106 @@
+use crate::commands::pwm::open_passwords_file_readonly;
+use crate::input::prompt_user_to_unlock_file_with_password;
+use crate::locker::{
+    error_message, print_error, EncryptedFile, Error, LockedEncryptedFile, Result,
+};
+use crate::passwords::PasswordIterator;
+use crate::styles::{error_style, success_style};
+use fallible_iterator::FallibleIterator;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+enum CheckResult {
+    // the amount of entries is only known if the entries were checked
+    Valid { entries: Option<usize> },
+    CorruptedTrashSection { error: Error },
+    // the index starts from 1, and the offset is the offset in the decrypted content where the
+    // entry starts
+    CorruptedEntry { index: usize, offset: usize, error: Error },
+}
+
+// unlocking the file verifies its headers, the password and the authentication tag, which covers
+// all of its content
+fn unlock(file: LockedEncryptedFile, prompt: &str) -> Result<EncryptedFile> {
+    file.verify_headers()?;
+    prompt_user_to_unlock_file_with_password(file, prompt)
+}
+fn check_entries(file: LockedEncryptedFile, prompt: &str) -> Result<CheckResult> {
+    let mut unlocked_file = unlock(file, prompt)?;
+    let mut iterator = PasswordIterator::new(&mut unlocked_file);
+    if let Err(error) = iterator.skip_trash_section() {
+        return Ok(CheckResult::CorruptedTrashSection { error });
+    }
+    let mut entries = 0;
+    loop {
+        let offset = iterator.offset();
+        match iterator.next() {
+            Ok(Some(_)) => entries += 1,
+            Ok(None) => break,
+            Err(error) => {
+                return Ok(CheckResult::CorruptedEntry {
+                    index: entries + 1,
+                    offset,
+                    error,
+                })
+            }
+        }
+    }
+    Ok(CheckResult::Valid {
+        entries: Some(entries),
+    })
+}
+// the content of a file that isn't a passwords file has no structure to check
+fn check(file: LockedEncryptedFile, prompt: &str) -> Result<CheckResult> {
+    unlock(file, prompt)?;
+    Ok(CheckResult::Valid { entries: None })
+}
+pub fn check_command(path: Option<PathBuf>, check_entries_of_path: bool) -> ExitCode {
+    let (result, file_prefix) = match path {
+        Some(path) => {
+            let check = if check_entries_of_path { check_entries } else { check };
+            (
+                LockedEncryptedFile::open_readonly(path)
+                    .and_then(|file| check(file, "Enter password: ")),
+                "target",
+            )
+        }
+        None => (
+            open_passwords_file_readonly()
+                .and_then(|file| check_entries(file, "Enter master password: ")),
+            "passwords",
+        ),
+    };
+    let problem = match result {
+        Ok(CheckResult::Valid { entries }) => {
+            let message = match entries {
+                Some(entries) => format!(
+                    "The {} file is valid, all of its {} entries were read successfully",
+                    file_prefix, entries
+                ),
+                None => format!("The {} file is valid", file_prefix),
+            };
+            println!("{}", success_style().paint(message));
+            return ExitCode::SUCCESS;
+        }
+        Ok(CheckResult::CorruptedTrashSection { error }) => format!(
+            "The trash section is invalid: {}",
+            error_message(&error, file_prefix)
+        ),
+        Ok(CheckResult::CorruptedEntry {
+            index,
+            offset,
+            error,
+        }) => format!(
+            "Entry {} (at offset {} of the content) is invalid: {}",
+            index,
+            offset,
+            error_message(&error, file_prefix)
+        ),
+        Err(e) => {
+            print_error(e, file_prefix, &error_style());
+            return ExitCode::FAILURE;
+        }
+    };
+    eprintln!("{}", error_style().paint(problem));
+    ExitCode::FAILURE
+}
@@ -0,0 +1,24 @@
+use crate::cli::Cli;
+use crate::styles::error_style;
+use clap::CommandFactory;
+use clap_complete::Shell;
+use std::io::{ErrorKind, Write};
+use std::process::ExitCode;
+
+pub fn completions_command(shell: Shell) -> ExitCode {
+    // generating straight into stdout panics once it's closed, for example when piped into `head`
+    let mut script = Vec::new();
+    clap_complete::generate(shell, &mut Cli::command(), "pwm", &mut script);
+    match std::io::stdout().write_all(&script) {
+        Ok(()) => ExitCode::SUCCESS,
+        // whoever reads the script has stopped reading it, which isn't an error
+        Err(err) if err.kind() == ErrorKind::BrokenPipe => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!(
+                "{}",
+                error_style().paint(format!("Failed to write the completion script: {}", err))
+            );
+            ExitCode::FAILURE
+        }
+    }
+}
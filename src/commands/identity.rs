@@ -0,0 +1,59 @@
+use crate::config::Config;
+use crate::locker::{print_error, public_key_to_hex, ErrorKind, Identity, Result};
+use crate::styles::{error_style, success_style, warning_style};
+use std::process::ExitCode;
+
+// the identity of the user, which must have been created
+pub fn load_identity() -> Result<Identity> {
+    let path = Config::load()?.identity_path()?;
+    if !path.exists() {
+        return Err(ErrorKind::NoIdentity.without_source_error());
+    }
+    Identity::read(path)
+}
+
+fn identity_new() -> Result<Identity> {
+    let path = Config::load()?.identity_path()?;
+    // replacing the identity would lose access to everything that was encrypted to it
+    if path.exists() {
+        return Err(ErrorKind::IdentityExists.without_source_error());
+    }
+    Identity::generate(path)
+}
+
+pub fn identity_new_command() -> ExitCode {
+    match identity_new() {
+        Ok(identity) => {
+            println!(
+                "{}",
+                success_style().paint("Your identity was successfully created, its public key is:")
+            );
+            println!("{}", public_key_to_hex(&identity.public_key()));
+            println!(
+                "{}",
+                warning_style().paint(
+                    "Back up the identity file, whatever is encrypted to it can't be decrypted \
+                     without it"
+                )
+            );
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            print_error(e, "identity", &error_style());
+            ExitCode::FAILURE
+        }
+    }
+}
+
+pub fn identity_show_command() -> ExitCode {
+    match load_identity() {
+        Ok(identity) => {
+            println!("{}", public_key_to_hex(&identity.public_key()));
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            print_error(e, "identity", &error_style());
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,102 @@
+use crate::commands::pwm::{
+    file_prefix, open_file_for_writing, unlock_file_with_password_for_writing,
+};
+use crate::locker::{print_error, EncryptedFile, ErrorKind, KeyFile, Result};
+use crate::secret::Secret;
+use crate::styles::{error_style, success_style};
+use std::path::Path;
+use std::process::ExitCode;
+
+// the key file requirement belongs to the key slot that the password unlocks, the other key slots
+// are left as they are
+fn unlocked_key_slot_requires_key_file(file: &EncryptedFile) -> bool {
+    match file.unlocked_key_slot() {
+        Some(index) => file.key_slots()[index].requires_key_file(),
+        None => false,
+    }
+}
+
+// the key slot is replaced with one derived from the same password and with the same kdf params
+fn change_key_file(
+    file: &mut EncryptedFile,
+    password: &Secret<String>,
+    key_file: Option<KeyFile>,
+) -> Result<()> {
+    file.set_key_file(key_file);
+    let kdf_params = file.kdf_params();
+    file.change_key(password, kdf_params)
+}
+
+fn keyfile_add(key_file_path: &Path, path: Option<&Path>, generate: bool) -> Result<()> {
+    let (file, is_passwords_file) = open_file_for_writing(path)?;
+    let (mut unlocked_file, password) =
+        unlock_file_with_password_for_writing(file, is_passwords_file)?;
+    if unlocked_key_slot_requires_key_file(&unlocked_file) {
+        return Err(ErrorKind::KeyFileAlreadyRequired.without_source_error());
+    }
+    // the key file is only generated once the password is known to be right, so that a wrong
+    // password doesn't leave behind a key file that nothing requires
+    let key_file = match generate {
+        true => KeyFile::generate(key_file_path)?,
+        false => KeyFile::read(key_file_path)?,
+    };
+    change_key_file(&mut unlocked_file, &password, Some(key_file))
+}
+
+fn keyfile_remove(path: Option<&Path>, key_file_path: Option<&Path>) -> Result<()> {
+    let (mut file, is_passwords_file) = open_file_for_writing(path)?;
+    if !file.uses_key_file() {
+        return Err(ErrorKind::KeyFileNotRequired.without_source_error());
+    }
+    if let Some(key_file_path) = key_file_path {
+        file.set_key_file(KeyFile::read(key_file_path)?);
+    }
+    let (mut unlocked_file, password) =
+        unlock_file_with_password_for_writing(file, is_passwords_file)?;
+    if !unlocked_key_slot_requires_key_file(&unlocked_file) {
+        return Err(ErrorKind::KeyFileNotRequired.without_source_error());
+    }
+    change_key_file(&mut unlocked_file, &password, None)
+}
+
+pub fn keyfile_add_command(key_file_path: &Path, path: Option<&Path>, generate: bool) -> ExitCode {
+    let file_prefix = file_prefix(path);
+    match keyfile_add(key_file_path, path, generate) {
+        Ok(()) => {
+            println!(
+                "{}",
+                success_style().paint(format!(
+                    "The {} file now requires the key file {} to be unlocked with this password, \
+                     keep a copy of it since the password alone no longer unlocks the file",
+                    file_prefix,
+                    key_file_path.display()
+                ))
+            );
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            print_error(e, file_prefix, &error_style());
+            ExitCode::FAILURE
+        }
+    }
+}
+
+pub fn keyfile_remove_command(path: Option<&Path>, key_file_path: Option<&Path>) -> ExitCode {
+    let file_prefix = file_prefix(path);
+    match keyfile_remove(path, key_file_path) {
+        Ok(()) => {
+            println!(
+                "{}",
+                success_style().paint(format!(
+                    "The {} file no longer requires a key file to be unlocked with this password",
+                    file_prefix
+                ))
+            );
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            print_error(e, file_prefix, &error_style());
+            ExitCode::FAILURE
+        }
+    }
+}
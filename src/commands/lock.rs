@@ -1,16 +1,38 @@
-use crate::locker::{EncryptedFile,InnerFile,Result,print_error,MutableFile};
-use crate::styles::{error_style,success_style};
+use crate::config::Config;
+use crate::input::prompt_user_to_create_master_password;
+use crate::locker::{print_error, CipherSuite, KdfOptions, Result};
+use crate::styles::{error_style, success_style};
+use std::path::Path;
+use std::process::ExitCode;
 
-fn lock(path:&str,key:&str,make_immutable:bool)->Result<()>{
-    let mut file=EncryptedFile::encrypt_file(path, key)?;
-    if make_immutable{
-        file.inner_file_mut().make_immutable()?;
-    }
-    Ok(())
+// the cipher suite in the config file is used if none is given
+fn lock(
+    path: &Path,
+    kdf_options: KdfOptions,
+    cipher_suite: Option<CipherSuite>,
+    make_immutable: bool,
+) -> Result<()> {
+    let cipher_suite = match cipher_suite {
+        Some(cipher_suite) => cipher_suite,
+        None => Config::load()?.cipher_suite,
+    };
+    let key = prompt_user_to_create_master_password("Enter a password for the target file: ")?;
+    crate::locker::lock(path, &key, kdf_options.params()?, cipher_suite, make_immutable)
 }
-pub fn lock_command(path:&str,key:&str,make_immutable:bool){
-    match lock(path,key,make_immutable){
-        Ok(())=>println!("{}",success_style().paint("The target file was successfully locked")),
-        Err(e)=>print_error(e, "target", &error_style()),
+pub fn lock_command(
+    path: &Path,
+    kdf_options: KdfOptions,
+    cipher_suite: Option<CipherSuite>,
+    make_immutable: bool,
+) -> ExitCode {
+    match lock(path, kdf_options, cipher_suite, make_immutable) {
+        Ok(()) => {
+            println!("{}", success_style().paint("The target file was successfully locked"));
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            print_error(e, "target", &error_style());
+            ExitCode::FAILURE
+        }
     }
-}
\ No newline at end of file
+}
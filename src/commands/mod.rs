@@ -1,6 +1,22 @@
+pub mod calibrate;
+pub mod check;
+pub mod completions;
+pub mod identity;
+pub mod keyfile;
 pub mod lock;
+pub mod recipients;
+pub mod slot;
 pub mod unlock;
+pub mod upgrade;
+pub use calibrate::calibrate_command;
+pub use check::check_command;
+pub use completions::completions_command;
+pub use identity::{identity_new_command, identity_show_command};
+pub use keyfile::{keyfile_add_command, keyfile_remove_command};
 pub use lock::lock_command;
+pub use recipients::{recipients_add_command, recipients_remove_command};
+pub use slot::{slot_add_command, slot_list_command, slot_revoke_command, NewKeySlot};
 pub use unlock::unlock_command;
+pub use upgrade::upgrade_command;
 
-pub mod pwm;
\ No newline at end of file
+pub mod pwm;
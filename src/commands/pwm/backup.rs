@@ -0,0 +1,75 @@
+use crate::{
+    input::{prompt_user_to_confirm, prompt_user_to_unlock_file_with_password},
+    locker::{print_error, LockedEncryptedFile, Result},
+    styles::{error_style, success_style, warning_style},
+};
+use std::process::ExitCode;
+
+use super::{get_passwords_file_path, passwords_file_backups, printing::format_time_ago};
+
+fn backup_list() -> Result<()> {
+    let backups = passwords_file_backups()?.list()?;
+    if backups.is_empty() {
+        println!("{}", warning_style().paint("There are no backups"));
+    }
+    for backup in backups {
+        println!(
+            "{} (made {})",
+            success_style().paint(backup.id.to_string()),
+            format_time_ago(backup.created_at())
+        );
+    }
+    Ok(())
+}
+
+pub fn backup_list_command() -> ExitCode {
+    match backup_list() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            print_error(e, "passwords", &error_style());
+            ExitCode::FAILURE
+        }
+    }
+}
+
+// returns false if the user didn't confirm the restore
+fn backup_restore(id: u64, skip_confirmation: bool) -> Result<bool> {
+    let backups = passwords_file_backups()?;
+    let backup = backups.find(id)?;
+
+    // make sure that the backup can be unlocked before replacing the passwords file with it
+    prompt_user_to_unlock_file_with_password(
+        LockedEncryptedFile::open_readonly(&backup.path)?,
+        "Enter the master password of the backup: ",
+    )?;
+    if !skip_confirmation
+        && !prompt_user_to_confirm(&format!(
+            "Replace the passwords file with the backup made {}?",
+            format_time_ago(backup.created_at())
+        ))?
+    {
+        return Ok(false);
+    }
+    backups.restore(&backup, &get_passwords_file_path()?)?;
+    Ok(true)
+}
+
+pub fn backup_restore_command(id: u64, skip_confirmation: bool) -> ExitCode {
+    match backup_restore(id, skip_confirmation) {
+        Ok(true) => {
+            println!(
+                "{}",
+                success_style().paint("The passwords file was successfully restored from the backup")
+            );
+            ExitCode::SUCCESS
+        }
+        Ok(false) => {
+            println!("{}", warning_style().paint("The passwords file was not restored"));
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            print_error(e, "passwords", &error_style());
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,103 @@
+use crate::{
+    input::{
+        prompt_user_to_confirm, prompt_user_to_create_master_password,
+        prompt_user_to_enter_password,
+    },
+    locker::{print_error, EncryptedFlush, ErrorKind, Result},
+    passwords::{PasswordWriter, TrashWriter},
+    styles::{error_style, success_style, warning_style},
+};
+use std::process::ExitCode;
+
+use super::{open_passwords_file_for_writing, unlock_file_with_password_for_writing};
+
+// returns false if the user didn't confirm replacing the decoy vault
+fn decoy_create(skip_confirmation: bool) -> Result<bool> {
+    let file = open_passwords_file_for_writing()?;
+    let (mut unlocked_file, master_password) = unlock_file_with_password_for_writing(file, true)?;
+    // whether the passwords file already has a decoy vault can't be told without its password, so
+    // the user is always asked
+    if !skip_confirmation
+        && !prompt_user_to_confirm(
+            "Create a new empty decoy vault, replacing the current one if there is one?",
+        )?
+    {
+        return Ok(false);
+    }
+    let duress_password =
+        prompt_user_to_create_master_password("Enter the duress password of the decoy vault: ")?;
+    if prompt_user_to_enter_password("Repeat the duress password: ")? != duress_password {
+        return Err(ErrorKind::PasswordsDontMatch.without_source_error());
+    }
+    // the key slots are tried before the decoy vault, so it would never be unlocked. the master
+    // password is compared first, since trying the key slots derives a key for each of them
+    if duress_password == master_password
+        || unlocked_file.password_unlocks_key_slot(&duress_password)
+    {
+        return Err(ErrorKind::DuressPasswordUnlocksKeySlot.without_source_error());
+    }
+    unlocked_file.create_hidden_vault(&duress_password)?;
+    let mut writer = unlocked_file.writer();
+    writer.write_trash_section(&[])?.write_passwords(&[])?;
+    writer.flush()?;
+    Ok(true)
+}
+
+// returns false if the user didn't confirm removing the decoy vault
+fn decoy_remove(skip_confirmation: bool) -> Result<bool> {
+    let file = open_passwords_file_for_writing()?;
+    let (mut unlocked_file, _) = unlock_file_with_password_for_writing(file, true)?;
+    if !skip_confirmation
+        && !prompt_user_to_confirm(
+            "Destroy the decoy vault and its entries, if there is one? This can't be undone",
+        )?
+    {
+        return Ok(false);
+    }
+    unlocked_file.remove_hidden_vault()?;
+    Ok(true)
+}
+
+pub fn decoy_create_command(skip_confirmation: bool) -> ExitCode {
+    match decoy_create(skip_confirmation) {
+        Ok(true) => {
+            println!(
+                "{}",
+                success_style().paint(
+                    "The decoy vault was successfully created, entering the duress password \
+                     instead of the master password opens it"
+                )
+            );
+            ExitCode::SUCCESS
+        }
+        Ok(false) => {
+            println!("{}", warning_style().paint("The decoy vault was not created"));
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            print_error(e, "passwords", &error_style());
+            ExitCode::FAILURE
+        }
+    }
+}
+
+pub fn decoy_remove_command(skip_confirmation: bool) -> ExitCode {
+    match decoy_remove(skip_confirmation) {
+        Ok(true) => {
+            println!(
+                "{}",
+                success_style()
+                    .paint("The hidden region of the passwords file was refilled with random bytes")
+            );
+            ExitCode::SUCCESS
+        }
+        Ok(false) => {
+            println!("{}", warning_style().paint("The decoy vault was not removed"));
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            print_error(e, "passwords", &error_style());
+            ExitCode::FAILURE
+        }
+    }
+}
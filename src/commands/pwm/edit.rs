@@ -0,0 +1,118 @@
+use std::collections::HashMap;
+
+use crate::{
+    editor::edit_in_editor,
+    input::prompt_user_to_enter_password,
+    locker::{print_error, EncryptedFile, ErrorKind, Result},
+    passwords::{
+        generate_password, Password, PasswordFilter, PasswordGeneratorOptions,
+        PasswordsFileContent,
+    },
+    styles::{error_style, success_style},
+    wrapped_clipboard,
+};
+use std::process::ExitCode;
+
+use super::unlock_passwords_file_for_writing;
+
+pub enum PasswordChange {
+    Keep,
+    Prompt,
+    Generate(PasswordGeneratorOptions),
+}
+
+pub struct EntryChanges {
+    pub password: PasswordChange,
+    pub username: Option<String>,
+    pub domain: Option<String>,
+    pub set_fields: HashMap<String, String>,
+    pub remove_fields: Vec<String>,
+    // fields whose value should be edited in the user's editor, used for multi-line values
+    pub edit_fields: Vec<String>,
+}
+
+// returns true if the password of the entry was newly generated
+fn apply_changes(password: &mut Password, changes: EntryChanges) -> Result<bool> {
+    let mut is_password_generated = false;
+    match changes.password {
+        PasswordChange::Keep => {}
+        PasswordChange::Prompt => {
+            password.password = prompt_user_to_enter_password("Enter the new password: ")?
+        }
+        PasswordChange::Generate(options) => {
+            password.password = generate_password(&options)?;
+            wrapped_clipboard::clipboard_set(&password.password)?;
+            is_password_generated = true;
+        }
+    }
+    if let Some(username) = changes.username {
+        password.username = username;
+    }
+    if let Some(domain) = changes.domain {
+        password.domain = domain;
+    }
+    for field_name in &changes.remove_fields {
+        password.additional_fields.remove(field_name);
+    }
+    password.additional_fields.extend(changes.set_fields);
+    for field_name in changes.edit_fields {
+        let current_value = password
+            .additional_fields
+            .get(&field_name)
+            .map(String::as_str)
+            .unwrap_or("");
+        let new_value = edit_in_editor(current_value)?;
+        password.additional_fields.insert(field_name, new_value);
+    }
+    Ok(is_password_generated)
+}
+
+pub fn edit_password_in_unlocked_file(
+    file: &mut EncryptedFile,
+    filter: &PasswordFilter,
+    changes: EntryChanges,
+) -> Result<bool> {
+    let mut content = PasswordsFileContent::read(file)?;
+    let mut matching = content
+        .passwords
+        .iter_mut()
+        .filter(|password| filter.test(password));
+    let password = match (matching.next(), matching.next()) {
+        (Some(password), None) => password,
+        (None, _) => return Err(ErrorKind::NoMatchingEntry.without_source_error()),
+        (Some(_), Some(_)) => {
+            return Err(ErrorKind::MultipleMatchingEntries.without_source_error())
+        }
+    };
+    let is_password_generated = apply_changes(password, changes)?;
+    content.write(file)?;
+    Ok(is_password_generated)
+}
+
+// returns true if the password of the entry was newly generated
+fn edit(filter: PasswordFilter, changes: EntryChanges) -> Result<bool> {
+    let mut unlocked_file = unlock_passwords_file_for_writing(None)?;
+    edit_password_in_unlocked_file(&mut unlocked_file, &filter, changes)
+}
+
+pub fn edit_command(filter: PasswordFilter, changes: EntryChanges) -> ExitCode {
+    match edit(filter, changes) {
+        Ok(was_password_generated) => {
+            if was_password_generated {
+                println!(
+                    "{}",
+                    success_style().paint(
+                        "The entry was successfully edited and its new password was copied to the clipboard"
+                    )
+                )
+            } else {
+                println!("{}", success_style().paint("The entry was successfully edited"))
+            }
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            print_error(e, "passwords", &error_style());
+            ExitCode::FAILURE
+        }
+    }
+}
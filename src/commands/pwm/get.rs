@@ -1,13 +1,14 @@
 use crate::{
-    wrapped_clipboard,
     input::prompt_user_to_unlock_file_with_password,
-    locker::{print_error, EncryptedFile, LockedEncryptedFile, MutableFile, Result},
+    locker::{print_error, EncryptedFile, KeyFile, Result},
     passwords::{Filter, PasswordFilter, PasswordIterator, Sort, SortBy},
     styles::error_style,
 };
+use std::path::Path;
+use std::process::ExitCode;
 
 use super::{
-    create_passwords_file_dialog, get_passwords_file_path,
+    create_passwords_file_dialog, get_passwords_file_path, open_passwords_file_readonly,
     printing::{print_passwords, print_sorted_passwords, PrintingMode},
 };
 pub fn get_passwords_from_unlocked_file(
@@ -27,28 +28,37 @@ pub fn get_passwords_from_unlocked_file(
     };
     Ok(())
 }
-fn get(filter: PasswordFilter, sort_by: Option<SortBy>, printing_mode: PrintingMode) -> Result<()> {
+fn get(
+    filter: PasswordFilter,
+    sort_by: Option<SortBy>,
+    printing_mode: PrintingMode,
+    key_file: Option<&Path>,
+) -> Result<()> {
     let path = get_passwords_file_path()?;
     let mut unlocked_file = if path.exists() {
-        prompt_user_to_unlock_file_with_password(
-            LockedEncryptedFile::open_readonly(path)?,
-            "Enter master password: ",
-        )?
+        let mut file = open_passwords_file_readonly()?;
+        if let Some(key_file) = key_file {
+            file.set_key_file(KeyFile::read(key_file)?);
+        }
+        prompt_user_to_unlock_file_with_password(file, "Enter master password: ")?
     } else {
         create_passwords_file_dialog(&path)?;
         return Ok(());
     };
-    match get_passwords_from_unlocked_file(&mut unlocked_file, filter, sort_by, printing_mode) {
-        Ok(()) => Ok(()),
-        Err(err) => {
-            unlocked_file.make_immutable()?;
-            Err(err)
-        }
-    }
+    get_passwords_from_unlocked_file(&mut unlocked_file, filter, sort_by, printing_mode)
 }
 
-pub fn get_command(filter: PasswordFilter, sort_by: Option<SortBy>, printing_mode: PrintingMode) {
-    if let Err(error) = get(filter, sort_by, printing_mode) {
-        print_error(error, "passwords", &error_style());
+pub fn get_command(
+    filter: PasswordFilter,
+    sort_by: Option<SortBy>,
+    printing_mode: PrintingMode,
+    key_file: Option<&Path>,
+) -> ExitCode {
+    match get(filter, sort_by, printing_mode, key_file) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            print_error(error, "passwords", &error_style());
+            ExitCode::FAILURE
+        }
     }
 }
@@ -1,19 +1,45 @@
+mod backup;
+mod decoy;
+pub mod edit;
 mod get;
 mod new;
+mod passwd;
+mod remove;
+mod salvage;
+mod shares;
+mod share;
+mod trash;
 pub mod master_password;
 pub mod printing;
 
-pub use {get::get_command,new::new_command};
+pub use {
+    backup::{backup_list_command, backup_restore_command},
+    decoy::{decoy_create_command, decoy_remove_command},
+    edit::edit_command,
+    get::get_command,
+    new::new_command,
+    passwd::passwd_command,
+    remove::remove_command,
+    salvage::salvage_command,
+    share::{receive_command, share_command},
+    shares::{recover_command, split_command},
+    trash::{trash_list_command, trash_purge_command, trash_restore_command},
+};
 
 use crate::{
-    input::prompt_user_to_create_master_password,
-    locker::{EncryptedFile, ErrorKind, Result, EncryptedFlush},
-    passwords::PasswordWriter,
+    input::{
+        prompt_user_for_file_password, prompt_user_to_create_master_password,
+        prompt_user_to_unlock_file_with_password,
+    },
+    config::Config,
+    locker::{error_message, Backups, EncryptedFile, ErrorKind, KdfParams, KeyFile, LockedEncryptedFile, Result, EncryptedFlush, FORMAT_VERSION},
+    passwords::{PasswordWriter, PasswordsFileContent, TrashWriter},
+    secret::Secret,
     styles::{success_style, warning_style},
 };
 use std::path::{Path, PathBuf};
 
-fn get_passwords_file_path() -> Result<PathBuf> {
+pub fn get_passwords_file_path() -> Result<PathBuf> {
     match dirs::home_dir() {
         Some(mut dir) => {
             dir.push(".pswm");
@@ -23,15 +49,128 @@ fn get_passwords_file_path() -> Result<PathBuf> {
     }
 }
 
+fn get_backups_dir_path() -> Result<PathBuf> {
+    match dirs::home_dir() {
+        Some(mut dir) => {
+            dir.push(".pswm-backups");
+            Ok(dir)
+        }
+        None => Err(ErrorKind::HomeDir.without_source_error()),
+    }
+}
+
+// other users must not be able to read or replace the passwords file, unless strict permissions
+// are set in the config this only causes a warning
+fn verify_passwords_file_permissions(file: &LockedEncryptedFile) -> Result<()> {
+    match file.verify_private() {
+        Err(e)
+            if matches!(
+                e.kind(),
+                ErrorKind::FileOwnedByAnotherUser | ErrorKind::FileAccessibleByOthers { .. }
+            ) && !Config::load()?.strict_permissions =>
+        {
+            println!(
+                "{}",
+                warning_style().paint(format!("Warning: {}", error_message(&e, "passwords")))
+            );
+            Ok(())
+        }
+        result => result,
+    }
+}
+
+pub fn open_passwords_file_readonly() -> Result<LockedEncryptedFile> {
+    let file = LockedEncryptedFile::open_readonly(get_passwords_file_path()?)?;
+    verify_passwords_file_permissions(&file)?;
+    Ok(file)
+}
+
+pub fn open_passwords_file_for_writing() -> Result<LockedEncryptedFile> {
+    let file = LockedEncryptedFile::open_write(get_passwords_file_path()?)?;
+    verify_passwords_file_permissions(&file)?;
+    Ok(file)
+}
+
+pub fn passwords_file_backups() -> Result<Backups> {
+    Ok(Backups::new(
+        get_backups_dir_path()?,
+        Config::load()?.backup_retention,
+    ))
+}
+
+// rewrites the entries of a passwords file that was written in an older format in the current
+// format, which must be done before anything is written to it
+pub fn migrate_passwords_file(file: &mut EncryptedFile) -> Result<()> {
+    if file.format_version() == FORMAT_VERSION {
+        return Ok(());
+    }
+    PasswordsFileContent::read(file)?.write(file)
+}
+
+// the key file is only needed if the passwords file requires one, otherwise the one in the config
+// file is used
+fn unlock_passwords_file_for_writing(key_file: Option<&Path>) -> Result<EncryptedFile> {
+    let mut locked_file = open_passwords_file_for_writing()?;
+    if let Some(key_file) = key_file {
+        locked_file.set_key_file(KeyFile::read(key_file)?);
+    }
+    let mut file = prompt_user_to_unlock_file_with_password(locked_file, "Enter master password: ")?;
+    file.set_backups(passwords_file_backups()?);
+    migrate_passwords_file(&mut file)?;
+    Ok(file)
+}
+
+// the prefix of the file in error messages, for commands that take the path of a file and default
+// to the passwords file
+pub fn file_prefix(path: Option<&Path>) -> &'static str {
+    match path {
+        Some(_) => "target",
+        None => "passwords",
+    }
+}
+
+// opens the given file, or the passwords file if no path is given. returns whether the opened file
+// is the passwords file
+pub fn open_file_for_writing(path: Option<&Path>) -> Result<(LockedEncryptedFile, bool)> {
+    match path {
+        Some(path) => Ok((LockedEncryptedFile::open_write(path)?, false)),
+        None => Ok((open_passwords_file_for_writing()?, true)),
+    }
+}
+
+// the password is returned along with the unlocked file, for changing the key slots of the file,
+// which derive keys from it
+pub fn unlock_file_with_password_for_writing(
+    mut file: LockedEncryptedFile,
+    is_passwords_file: bool,
+) -> Result<(EncryptedFile, Secret<String>)> {
+    let prompt = match is_passwords_file {
+        true => "Enter master password: ",
+        false => "Enter password: ",
+    };
+    let password = prompt_user_for_file_password(&mut file, prompt)?;
+    let mut unlocked_file = file.unlock(&password)?;
+    if is_passwords_file {
+        unlocked_file.set_backups(passwords_file_backups()?);
+        migrate_passwords_file(&mut unlocked_file)?;
+    }
+    Ok((unlocked_file, password))
+}
+
 pub fn create_passwords_file_dialog(path: &Path) -> Result<EncryptedFile> {
     println!("{}", warning_style().paint("Passwords file not found"));
     let master_password = prompt_user_to_create_master_password(
         "Enter a master password for the new passwords file: ",
     )?;
-    let mut file = EncryptedFile::create(path, &master_password)?;
+    let mut file = EncryptedFile::create(
+        path,
+        &master_password,
+        KdfParams::DEFAULT,
+        Config::load()?.cipher_suite,
+    )?;
     {
         let mut writer = file.writer();
-        writer.write_passwords(&[]);
+        writer.write_trash_section(&[])?.write_passwords(&[])?;
         writer.flush()?;
         println!(
             "{}",
@@ -1,41 +1,40 @@
 use std::collections::HashMap;
 
-use crate::{wrapped_clipboard,input::prompt_user_to_unlock_file_with_password, locker::{
-        print_error, EncryptedFile, EncryptedFlush, LockedEncryptedFile, MutableFile, Result,
+use crate::{wrapped_clipboard,input::prompt_user_to_enter_password, locker::{
+        print_error, EncryptedFile, EncryptedFlush, Result,
     }, passwords::{
-        generate_password, Filter, Password, PasswordFilter, PasswordGeneratorOptions,
-        PasswordWriter, Sort, SortBy,
+        generate_password, Password, PasswordGeneratorOptions, PasswordWriter,
     }, styles::{error_style, success_style}};
+use std::path::Path;
+use std::process::ExitCode;
 
-use super::{create_passwords_file_dialog, get_passwords_file_path};
+use super::{create_passwords_file_dialog, get_passwords_file_path, unlock_passwords_file_for_writing};
 pub fn add_password_to_unlocked_file(file: &mut EncryptedFile, password: &Password) -> Result<()> {
     let mut appender = file.appender();
-    appender.write_password(&password);
+    appender.write_password(password)?;
     appender.flush()
 }
 // returns true if the new password was newly generated,
 // otherwise if it was supplied the function returns false
 fn new(
-    password: Option<String>,
+    prompt_for_password: bool,
     username: String,
     domain: String,
     additional_fields: HashMap<String, String>,
     password_generator_options: PasswordGeneratorOptions,
+    key_file: Option<&Path>,
 ) -> Result<bool> {
     let mut is_password_generated=false;
     let path = get_passwords_file_path()?;
     let mut unlocked_file = if path.exists() {
-        prompt_user_to_unlock_file_with_password(
-            LockedEncryptedFile::open_write(path)?,
-            "Enter master password: ",
-        )?
+        unlock_passwords_file_for_writing(key_file)?
     } else {
         create_passwords_file_dialog(&path)?
     };
     let password = Password {
-        password: match password {
-            Some(p) => p,
-            None => {
+        password: match prompt_for_password {
+            true => prompt_user_to_enter_password("Enter the password of the new entry: ")?,
+            false => {
                 let pwd=generate_password(&password_generator_options)?;
                 is_password_generated=true;
                 wrapped_clipboard::clipboard_set(&pwd)?;
@@ -46,28 +45,25 @@ fn new(
         domain,
         additional_fields,
     };
-    match add_password_to_unlocked_file(&mut unlocked_file, &password) {
-        Ok(()) => Ok(is_password_generated),
-        Err(err) => {
-            unlocked_file.make_immutable()?;
-            Err(err)
-        }
-    }
+    add_password_to_unlocked_file(&mut unlocked_file, &password)?;
+    Ok(is_password_generated)
 }
 
 pub fn new_command(
-    password: Option<String>,
+    prompt_for_password: bool,
     username: String,
     domain: String,
     additional_fields: HashMap<String, String>,
     password_generator_options: PasswordGeneratorOptions,
-) {
+    key_file: Option<&Path>,
+) -> ExitCode {
     match new(
-        password,
+        prompt_for_password,
         username,
         domain,
         additional_fields,
         password_generator_options,
+        key_file,
     ) {
         Ok(was_password_generated) => {
             if was_password_generated {
@@ -83,7 +79,11 @@ pub fn new_command(
                         .paint("The password was successfully added to the passwords file")
                 )
             }
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            print_error(e, "passwords", &error_style());
+            ExitCode::FAILURE
         }
-        Err(e) => print_error(e, "passwords", &error_style()),
     }
 }
@@ -0,0 +1,35 @@
+use crate::{
+    input::{prompt_user_to_create_master_password, prompt_user_to_enter_password},
+    locker::{print_error, ErrorKind, KdfOptions, Result},
+    styles::{error_style, success_style},
+};
+use std::process::ExitCode;
+
+use super::unlock_passwords_file_for_writing;
+
+fn passwd(kdf_options: KdfOptions) -> Result<()> {
+    let mut unlocked_file = unlock_passwords_file_for_writing(None)?;
+    let new_master_password =
+        prompt_user_to_create_master_password("Enter the new master password: ")?;
+    if prompt_user_to_enter_password("Repeat the new master password: ")? != new_master_password {
+        return Err(ErrorKind::PasswordsDontMatch.without_source_error());
+    }
+    let kdf_params = kdf_options.params()?;
+    unlocked_file.change_key(&new_master_password, kdf_params)
+}
+
+pub fn passwd_command(kdf_options: KdfOptions) -> ExitCode {
+    match passwd(kdf_options) {
+        Ok(()) => {
+            println!(
+                "{}",
+                success_style().paint("The master password was successfully changed")
+            );
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            print_error(e, "passwords", &error_style());
+            ExitCode::FAILURE
+        }
+    }
+}
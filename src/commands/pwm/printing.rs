@@ -1,6 +1,7 @@
-use crate::{locker::{Error, Result}, passwords::{SortBy, SortedPasswords}, styles::{success_style, warning_style}, wrapped_clipboard};
+use crate::{locker::{Error, Result}, passwords::SortedPasswords, styles::{success_style, warning_style}, wrapped_clipboard};
 use crate::{passwords::Password, styles::PasswordPrintingStyles};
 use fallible_iterator::FallibleIterator;
+use std::time::{SystemTime, UNIX_EPOCH};
 pub enum PrintingMode {
     Normal,
     Verbose,
@@ -62,6 +63,16 @@ pub fn print_sorted_passwords(sorted_passwords: SortedPasswords, printing_mode:
     Ok(())
 }
 
+pub fn print_password_summary(password: &Password) {
+    let styles = crate::styles::passwords_printing_styles();
+    println!(
+        "{}{}{}",
+        styles.username_style.paint(&password.username),
+        styles.at_symbol_style.paint("@"),
+        styles.domain_style.paint(&password.domain),
+    );
+}
+
 fn print_single_password(
     password: &Password,
     printing_mode: &PrintingMode,
@@ -74,7 +85,7 @@ fn print_single_password(
                 styles.username_style.paint(&password.username),
                 styles.at_symbol_style.paint("@"),
                 styles.domain_style.paint(&password.domain),
-                styles.password_style.paint(&password.password)
+                styles.password_style.paint(password.password.expose())
             );
         }
         PrintingMode::Verbose => {
@@ -94,7 +105,7 @@ fn print_single_password(
             println!(
                 " - {}: '{}'",
                 styles.password_style.paint("Password"),
-                styles.password_style.paint(&password.password)
+                styles.password_style.paint(password.password.expose())
             );
             println!();
         }
@@ -112,7 +123,7 @@ fn print_single_password_indented(
                 styles.username_style.paint(&password.username),
                 styles.at_symbol_style.paint("@"),
                 styles.domain_style.paint(&password.domain),
-                styles.password_style.paint(&password.password)
+                styles.password_style.paint(password.password.expose())
             );
         }
         PrintingMode::Verbose => {
@@ -132,9 +143,23 @@ fn print_single_password_indented(
             println!(
                 "\t - {}: '{}'",
                 styles.password_style.paint("Password"),
-                styles.password_style.paint(&password.password)
+                styles.password_style.paint(password.password.expose())
             );
             println!();
         }
     }
 }
+
+pub fn format_time_ago(timestamp: u64) -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+    let seconds = now.saturating_sub(timestamp);
+    match seconds {
+        0..=59 => "just now".to_string(),
+        60..=3599 => format!("{} minutes ago", seconds / 60),
+        3600..=86399 => format!("{} hours ago", seconds / 3600),
+        _ => format!("{} days ago", seconds / 86400),
+    }
+}
@@ -0,0 +1,68 @@
+use crate::{
+    input::prompt_user_to_confirm,
+    locker::{print_error, EncryptedFile, ErrorKind, Result},
+    passwords::{Password, PasswordFilter, PasswordsFileContent, TrashedPassword},
+    styles::{error_style, success_style, warning_style},
+};
+use std::process::ExitCode;
+
+use super::{printing::print_password_summary, unlock_passwords_file_for_writing};
+
+// returns the amount of removed passwords, which is 0 if the user didn't confirm the removal
+pub fn remove_passwords_from_unlocked_file(
+    file: &mut EncryptedFile,
+    filter: &PasswordFilter,
+    skip_confirmation: bool,
+) -> Result<usize> {
+    let mut content = PasswordsFileContent::read(file)?;
+    let (removed, kept): (Vec<Password>, Vec<Password>) = std::mem::take(&mut content.passwords)
+        .into_iter()
+        .partition(|password| filter.test(password));
+    if removed.is_empty() {
+        return Err(ErrorKind::NoMatchingEntry.without_source_error());
+    }
+
+    println!("The following entries will be moved to the trash:");
+    for password in &removed {
+        print_password_summary(password);
+    }
+    if !skip_confirmation && !prompt_user_to_confirm("Remove these entries?")? {
+        return Ok(0);
+    }
+
+    let amount = removed.len();
+    content.passwords = kept;
+    content
+        .trash
+        .extend(removed.into_iter().map(TrashedPassword::new));
+    content.write(file)?;
+    Ok(amount)
+}
+
+fn remove(filter: PasswordFilter, skip_confirmation: bool) -> Result<usize> {
+    let mut unlocked_file = unlock_passwords_file_for_writing(None)?;
+    remove_passwords_from_unlocked_file(&mut unlocked_file, &filter, skip_confirmation)
+}
+
+pub fn remove_command(filter: PasswordFilter, skip_confirmation: bool) -> ExitCode {
+    match remove(filter, skip_confirmation) {
+        Ok(0) => {
+            println!("{}", warning_style().paint("No entries were removed"));
+            ExitCode::SUCCESS
+        }
+        Ok(amount) => {
+            println!(
+                "{}",
+                success_style().paint(format!(
+                    "{} entries were moved to the trash, use `pwm trash restore` to restore them",
+                    amount
+                ))
+            );
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            print_error(e, "passwords", &error_style());
+            ExitCode::FAILURE
+        }
+    }
+}
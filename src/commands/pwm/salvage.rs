@@ -0,0 +1,111 @@
+use crate::{
+    input::prompt_user_for_file_password,
+    locker::{print_error, EncryptedFile, ErrorKind, LockedEncryptedFile, Result},
+    passwords::{salvage_passwords_file, SalvagedContent},
+    styles::{error_style, success_style, warning_style},
+};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use super::{open_passwords_file_readonly, printing::print_password_summary};
+
+fn print_salvaged_content(salvaged: &SalvagedContent) {
+    for password in &salvaged.content.passwords {
+        print!("Recovered ");
+        print_password_summary(password);
+    }
+    for trashed_password in &salvaged.content.trash {
+        print!("Recovered from the trash ");
+        print_password_summary(&trashed_password.password);
+    }
+    for skipped in &salvaged.skipped {
+        println!(
+            "{}",
+            warning_style().paint(format!(
+                "Skipped offsets {} to {} of the content, no entry could be read from them",
+                skipped.start, skipped.end
+            ))
+        );
+    }
+}
+
+// writes the entries that can be read from the source file to a new passwords file at the output
+// path, which is locked with the same password. returns the salvaged content
+fn salvage<F: FnOnce() -> Result<LockedEncryptedFile>>(
+    open_source: F,
+    output: &Path,
+    ignore_authentication: bool,
+) -> Result<SalvagedContent> {
+    // never overwrite a file, it might be another copy of the passwords file
+    if output.exists() {
+        return Err(ErrorKind::FileExists.without_source_error());
+    }
+    let mut file = open_source()?;
+    let password = prompt_user_for_file_password(&mut file, "Enter master password: ")?;
+    let mut unlocked_file = if ignore_authentication {
+        let (unlocked_file, authenticated) = file.unlock_unauthenticated(&password)?;
+        if !authenticated {
+            println!(
+                "{}",
+                warning_style().paint(
+                    "The file failed authentication, so it was decrypted without verifying it. \
+                     Its content might have been tampered with, review the recovered entries \
+                     before using them"
+                )
+            );
+        }
+        unlocked_file
+    } else {
+        file.unlock(&password)?
+    };
+    let salvaged = salvage_passwords_file(&mut unlocked_file);
+    print_salvaged_content(&salvaged);
+
+    let mut new_file = EncryptedFile::create(
+        output,
+        &password,
+        unlocked_file.kdf_params(),
+        unlocked_file.cipher_suite(),
+    )?;
+    salvaged.content.write(&mut new_file)?;
+    Ok(salvaged)
+}
+
+pub fn salvage_command(
+    source: Option<PathBuf>,
+    output: &Path,
+    ignore_authentication: bool,
+) -> ExitCode {
+    let (result, file_prefix) = match source {
+        Some(source) => (
+            salvage(
+                || LockedEncryptedFile::open_readonly(source),
+                output,
+                ignore_authentication,
+            ),
+            "target",
+        ),
+        None => (
+            salvage(open_passwords_file_readonly, output, ignore_authentication),
+            "passwords",
+        ),
+    };
+    match result {
+        Ok(salvaged) => {
+            println!(
+                "{}",
+                success_style().paint(format!(
+                    "{} entries and {} trashed entries were written to {}",
+                    salvaged.content.passwords.len(),
+                    salvaged.content.trash.len(),
+                    output.display()
+                ))
+            );
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            print_error(e, file_prefix, &error_style());
+            ExitCode::FAILURE
+        }
+    }
+}
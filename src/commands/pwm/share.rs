@@ -0,0 +1,81 @@
+use crate::{
+    commands::identity::load_identity,
+    input::prompt_user_to_unlock_file_with_password,
+    locker::{print_error, public_key_from_hex, ErrorKind, Result},
+    passwords::{
+        receive_password, share_password, Filter, Password, PasswordFilter, PasswordIterator,
+    },
+    styles::{error_style, success_style},
+};
+use fallible_iterator::FallibleIterator;
+use std::path::Path;
+use std::process::ExitCode;
+
+use super::{
+    new::add_password_to_unlocked_file, open_passwords_file_readonly,
+    unlock_passwords_file_for_writing,
+};
+
+fn share(public_key: &str, output: &Path, filter: PasswordFilter) -> Result<()> {
+    let public_key = public_key_from_hex(public_key)?;
+    let file = open_passwords_file_readonly()?;
+    let mut unlocked_file = prompt_user_to_unlock_file_with_password(file, "Enter master password: ")?;
+    let password = {
+        let mut matching = PasswordIterator::new(&mut unlocked_file).filter_passwords(filter);
+        match (matching.next()?, matching.next()?) {
+            (Some(password), None) => password,
+            (None, _) => return Err(ErrorKind::NoMatchingEntry.without_source_error()),
+            (Some(_), Some(_)) => {
+                return Err(ErrorKind::MultipleMatchingEntries.without_source_error())
+            }
+        }
+    };
+    share_password(output, &public_key, &password)
+}
+
+pub fn share_command(public_key: &str, output: &Path, filter: PasswordFilter) -> ExitCode {
+    match share(public_key, output, filter) {
+        Ok(()) => {
+            println!(
+                "{}",
+                success_style().paint(
+                    "The entry was successfully shared, only the owner of the public key can \
+                     receive it"
+                )
+            );
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            print_error(e, "passwords", &error_style());
+            ExitCode::FAILURE
+        }
+    }
+}
+
+// the entry is decrypted before the passwords file is unlocked, so that no password is asked for
+// a file that wasn't shared with the user
+fn receive(path: &Path) -> Result<Password> {
+    let password = receive_password(path, &load_identity()?)?;
+    let mut unlocked_file = unlock_passwords_file_for_writing(None)?;
+    add_password_to_unlocked_file(&mut unlocked_file, &password)?;
+    Ok(password)
+}
+
+pub fn receive_command(path: &Path) -> ExitCode {
+    match receive(path) {
+        Ok(password) => {
+            println!(
+                "{}",
+                success_style().paint(format!(
+                    "The entry of {} at {} was successfully added to the passwords file",
+                    password.username, password.domain
+                ))
+            );
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            print_error(e, "passwords", &error_style());
+            ExitCode::FAILURE
+        }
+    }
+}
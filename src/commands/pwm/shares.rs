@@ -0,0 +1,98 @@
+use crate::{
+    hardening::harden_process,
+    input::{prompt_user_to_create_master_password, prompt_user_to_enter_password},
+    locker::{print_error, recover_key, ErrorKind, KdfOptions, KeyShare, Result},
+    styles::{error_style, success_style, warning_style},
+};
+use std::process::ExitCode;
+
+use super::{
+    migrate_passwords_file, open_passwords_file_for_writing, passwords_file_backups,
+    unlock_passwords_file_for_writing,
+};
+
+fn split(shares: u8, threshold: u8) -> Result<Vec<KeyShare>> {
+    // unlocking the file for writing upgrades it to the current format, so that the data key that
+    // is split is the one in the file
+    let unlocked_file = unlock_passwords_file_for_writing(None)?;
+    unlocked_file.split_data_key(shares, threshold)
+}
+
+pub fn split_command(shares: u8, threshold: u8) -> ExitCode {
+    match split(shares, threshold) {
+        Ok(key_shares) => {
+            for (index, key_share) in key_shares.iter().enumerate() {
+                println!(
+                    "Share {} of {}: {}",
+                    index + 1,
+                    key_shares.len(),
+                    key_share.to_hex().expose()
+                );
+            }
+            println!(
+                "{}",
+                warning_style().paint(format!(
+                    "Give each share to a different person. Any {} of them unlock the passwords \
+                     file with `pwm recover`, even after its master password or key slots change",
+                    threshold
+                ))
+            );
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            print_error(e, "passwords", &error_style());
+            ExitCode::FAILURE
+        }
+    }
+}
+
+// the first share tells how many shares are needed. shares are secret, so they aren't echoed
+fn prompt_user_for_key_shares() -> Result<Vec<KeyShare>> {
+    let first_share = KeyShare::from_hex(&prompt_user_to_enter_password("Enter share 1: ")?)?;
+    let threshold = first_share.threshold as usize;
+    let mut key_shares = vec![first_share];
+    while key_shares.len() < threshold {
+        let prompt = format!("Enter share {} of {}: ", key_shares.len() + 1, threshold);
+        key_shares.push(KeyShare::from_hex(&prompt_user_to_enter_password(&prompt)?)?);
+    }
+    Ok(key_shares)
+}
+
+// returns the id of the key slot of the new master password
+fn recover(kdf_options: KdfOptions) -> Result<usize> {
+    let file = open_passwords_file_for_writing()?;
+    harden_process()?;
+    let data_key = recover_key(&prompt_user_for_key_shares()?)?;
+    let mut unlocked_file = file.unlock_with_data_key(data_key)?;
+    unlocked_file.set_backups(passwords_file_backups()?);
+    migrate_passwords_file(&mut unlocked_file)?;
+
+    let new_master_password =
+        prompt_user_to_create_master_password("Enter the new master password: ")?;
+    if prompt_user_to_enter_password("Repeat the new master password: ")? != new_master_password {
+        return Err(ErrorKind::PasswordsDontMatch.without_source_error());
+    }
+    // the file wasn't unlocked with a key slot, so a key slot is added for the new password
+    unlocked_file.change_key(&new_master_password, kdf_options.params()?)?;
+    Ok(unlocked_file.unlocked_key_slot().unwrap_or_default())
+}
+
+pub fn recover_command(kdf_options: KdfOptions) -> ExitCode {
+    match recover(kdf_options) {
+        Ok(id) => {
+            println!(
+                "{}",
+                success_style().paint(format!(
+                    "The passwords file was recovered, its new master password was added as key \
+                     slot {}. Revoke the key slots whose passwords were lost with `pwm slot revoke`",
+                    id
+                ))
+            );
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            print_error(e, "passwords", &error_style());
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use crate::{
+    input::{prompt_user_to_confirm, prompt_user_to_unlock_file_with_password},
+    locker::{print_error, EncryptedFile, ErrorKind, Result},
+    passwords::{PasswordFilter, PasswordsFileContent, TrashedPassword},
+    styles::{error_style, success_style, warning_style},
+};
+use std::process::ExitCode;
+
+use super::{
+    open_passwords_file_readonly,
+    printing::{format_time_ago, print_password_summary},
+    unlock_passwords_file_for_writing,
+};
+
+fn print_trashed_passwords(trash: &[TrashedPassword]) {
+    for trashed_password in trash {
+        print!("(removed {}) ", format_time_ago(trashed_password.removed_at));
+        print_password_summary(&trashed_password.password);
+    }
+}
+
+// takes the trashed passwords that match the filter out of the trash after the user confirms it.
+// returns None if the user didn't confirm
+fn take_matching_trashed_passwords(
+    content: &mut PasswordsFileContent,
+    filter: &PasswordFilter,
+    action: &str,
+    skip_confirmation: bool,
+) -> Result<Option<Vec<TrashedPassword>>> {
+    let (matching, rest): (Vec<TrashedPassword>, Vec<TrashedPassword>) =
+        std::mem::take(&mut content.trash)
+            .into_iter()
+            .partition(|trashed_password| filter.test(&trashed_password.password));
+    content.trash = rest;
+    if matching.is_empty() {
+        return Err(ErrorKind::NoMatchingEntry.without_source_error());
+    }
+    println!("The following entries will be {}:", action);
+    print_trashed_passwords(&matching);
+    if !skip_confirmation && !prompt_user_to_confirm("Continue?")? {
+        return Ok(None);
+    }
+    Ok(Some(matching))
+}
+
+fn run_on_passwords_file<T, F: FnOnce(&mut EncryptedFile) -> Result<T>>(f: F) -> Result<T> {
+    let mut unlocked_file = unlock_passwords_file_for_writing(None)?;
+    f(&mut unlocked_file)
+}
+
+fn print_amount_result(result: Result<usize>, success_message: &str) -> ExitCode {
+    match result {
+        Ok(0) => {
+            println!("{}", warning_style().paint("The trash wasn't changed"));
+            ExitCode::SUCCESS
+        }
+        Ok(amount) => {
+            println!(
+                "{}",
+                success_style().paint(format!("{} entries were {}", amount, success_message))
+            );
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            print_error(e, "passwords", &error_style());
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn trash_list() -> Result<()> {
+    let mut unlocked_file = prompt_user_to_unlock_file_with_password(
+        open_passwords_file_readonly()?,
+        "Enter master password: ",
+    )?;
+    let content = PasswordsFileContent::read(&mut unlocked_file)?;
+    if content.trash.is_empty() {
+        println!("{}", warning_style().paint("The trash is empty"));
+    } else {
+        print_trashed_passwords(&content.trash);
+    }
+    Ok(())
+}
+
+pub fn trash_list_command() -> ExitCode {
+    match trash_list() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            print_error(e, "passwords", &error_style());
+            ExitCode::FAILURE
+        }
+    }
+}
+
+pub fn trash_restore_command(filter: PasswordFilter, skip_confirmation: bool) -> ExitCode {
+    let result = run_on_passwords_file(|file| {
+        let mut content = PasswordsFileContent::read(file)?;
+        match take_matching_trashed_passwords(&mut content, &filter, "restored", skip_confirmation)? {
+            Some(restored) => {
+                let amount = restored.len();
+                content
+                    .passwords
+                    .extend(restored.into_iter().map(|trashed| trashed.password));
+                content.write(file)?;
+                Ok(amount)
+            }
+            None => Ok(0),
+        }
+    });
+    print_amount_result(result, "restored from the trash")
+}
+
+pub fn trash_purge_command(filter: PasswordFilter, skip_confirmation: bool) -> ExitCode {
+    let result = run_on_passwords_file(|file| {
+        let mut content = PasswordsFileContent::read(file)?;
+        match take_matching_trashed_passwords(
+            &mut content,
+            &filter,
+            "permanently deleted",
+            skip_confirmation,
+        )? {
+            Some(purged) => {
+                content.write(file)?;
+                Ok(purged.len())
+            }
+            None => Ok(0),
+        }
+    });
+    print_amount_result(result, "permanently deleted")
+}
@@ -0,0 +1,143 @@
+use crate::commands::pwm::{
+    file_prefix, open_file_for_writing, unlock_file_with_password_for_writing,
+};
+use crate::input::prompt_user_to_confirm;
+use crate::locker::{
+    print_error, public_key_from_hex, EncryptedFile, ErrorKind, KeyFile, Result,
+};
+use crate::passwords::PasswordsFileContent;
+use crate::secret::Secret;
+use crate::styles::{error_style, success_style, warning_style};
+use std::path::Path;
+use std::process::ExitCode;
+
+const DEFAULT_RECIPIENT_LABEL: &str = "recipient";
+
+// recipients are managed by the owners of the file, so it's unlocked with a password even if it's
+// also encrypted to the identity of the user. the password is needed to wrap a new data key
+fn unlock(
+    path: Option<&Path>,
+    key_file: Option<&Path>,
+) -> Result<(EncryptedFile, Secret<String>, bool)> {
+    let (mut file, is_passwords_file) = open_file_for_writing(path)?;
+    if let Some(key_file) = key_file {
+        file.set_key_file(KeyFile::read(key_file)?);
+    }
+    let (unlocked_file, password) = unlock_file_with_password_for_writing(file, is_passwords_file)?;
+    Ok((unlocked_file, password, is_passwords_file))
+}
+
+// returns the id of the key slot of the recipient
+fn recipients_add(
+    public_key: &str,
+    path: Option<&Path>,
+    label: Option<String>,
+    key_file: Option<&Path>,
+) -> Result<usize> {
+    let public_key = public_key_from_hex(public_key)?;
+    let (mut unlocked_file, _, _) = unlock(path, key_file)?;
+    let label = label.unwrap_or_else(|| DEFAULT_RECIPIENT_LABEL.to_string());
+    unlocked_file.add_recipient(label, &public_key)
+}
+
+// returns false if the user didn't confirm removing the recipient
+fn recipients_remove(
+    public_key: &str,
+    path: Option<&Path>,
+    key_file: Option<&Path>,
+    skip_confirmation: bool,
+) -> Result<bool> {
+    let public_key = public_key_from_hex(public_key)?;
+    let (mut unlocked_file, password, is_passwords_file) = unlock(path, key_file)?;
+    let index = match unlocked_file.recipient_key_slot(&public_key) {
+        Some(index) => index,
+        None => return Err(ErrorKind::NoSuchRecipient.without_source_error()),
+    };
+    let revoked: Vec<String> = unlocked_file
+        .other_password_key_slots()
+        .into_iter()
+        .map(|id| format!("{} ({})", id, unlocked_file.key_slots()[id].label))
+        .collect();
+    let revoked = match revoked.len() {
+        0 => String::new(),
+        1 => format!(
+            ", which revokes key slot {} since its password wasn't entered",
+            revoked[0]
+        ),
+        _ => format!(
+            ", which revokes key slots {} since their passwords weren't entered",
+            revoked.join(", ")
+        ),
+    };
+    if !skip_confirmation
+        && !prompt_user_to_confirm(&format!(
+            "Remove recipient {} ({}) and re-encrypt the file with a new key{}? Shares of the \
+             old key will no longer unlock it",
+            index,
+            unlocked_file.key_slots()[index].label,
+            revoked
+        ))?
+    {
+        return Ok(false);
+    }
+    // the entries of the passwords file are sealed with the data key, so they're read before it's
+    // replaced and sealed again with the new one
+    if is_passwords_file {
+        let content = PasswordsFileContent::read(&mut unlocked_file)?;
+        unlocked_file.remove_recipient(index, password.expose())?;
+        content.write(&mut unlocked_file)?;
+    } else {
+        unlocked_file.remove_recipient(index, password.expose())?;
+        unlocked_file.rewrite()?;
+    }
+    Ok(true)
+}
+
+pub fn recipients_add_command(
+    public_key: &str,
+    path: Option<&Path>,
+    label: Option<String>,
+    key_file: Option<&Path>,
+) -> ExitCode {
+    match recipients_add(public_key, path, label, key_file) {
+        Ok(id) => {
+            println!(
+                "{}",
+                success_style().paint(format!(
+                    "The recipient was successfully added as key slot {}",
+                    id
+                ))
+            );
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            print_error(e, file_prefix(path), &error_style());
+            ExitCode::FAILURE
+        }
+    }
+}
+
+pub fn recipients_remove_command(
+    public_key: &str,
+    path: Option<&Path>,
+    key_file: Option<&Path>,
+    skip_confirmation: bool,
+) -> ExitCode {
+    match recipients_remove(public_key, path, key_file, skip_confirmation) {
+        Ok(true) => {
+            println!(
+                "{}",
+                success_style().paint("The recipient was successfully removed")
+            );
+            ExitCode::SUCCESS
+        }
+        Ok(false) => {
+            println!("{}", warning_style().paint("The recipient was not removed"));
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            print_error(e, file_prefix(path), &error_style());
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,231 @@
+use crate::commands::pwm::{
+    file_prefix, open_file_for_writing, open_passwords_file_readonly,
+    unlock_file_with_password_for_writing,
+};
+use crate::input::{
+    prompt_user_to_confirm, prompt_user_to_create_master_password, prompt_user_to_enter_password,
+};
+use crate::locker::{
+    print_error, public_key_to_hex, EncryptedFile, ErrorKind, KdfOptions, KeyFile, KeySlot,
+    KeySlotKind, LockedEncryptedFile, Result, DEFAULT_KEY_SLOT_LABEL,
+};
+use crate::secret::Secret;
+use crate::styles::{error_style, success_style, warning_style};
+use rand::{thread_rng, RngCore};
+use std::fmt::Write;
+use std::path::Path;
+use std::process::ExitCode;
+
+const RECOVERY_CODE_SIZE: usize = 16;
+const RECOVERY_KEY_SLOT_LABEL: &str = "recovery";
+// the amount of bytes in each dash separated group of the recovery code
+const RECOVERY_CODE_GROUP_SIZE: usize = 2;
+
+// a random password for a key slot, which is meant to be kept offline in case the other
+// passwords are forgotten
+fn generate_recovery_code() -> Secret<String> {
+    let mut bytes = Secret::new([0u8; RECOVERY_CODE_SIZE]);
+    thread_rng().fill_bytes(bytes.expose_mut());
+    let mut recovery_code = Secret::new(String::with_capacity(RECOVERY_CODE_SIZE * 3));
+    for (index, byte) in bytes.iter().enumerate() {
+        if index > 0 && index % RECOVERY_CODE_GROUP_SIZE == 0 {
+            recovery_code.expose_mut().push('-');
+        }
+        // writing to a string can't fail
+        let _ = write!(recovery_code.expose_mut(), "{:02x}", byte);
+    }
+    recovery_code
+}
+
+fn print_key_slot(id: usize, key_slot: &KeySlot) {
+    let details = match &key_slot.kind {
+        KeySlotKind::Password(derivation) => {
+            let kdf_params = derivation.kdf_params;
+            let key_file = match derivation.requires_key_file() {
+                true => ", requires a key file",
+                false => "",
+            };
+            format!(
+                "{} MiB of memory, {} iterations, {} lanes{}",
+                kdf_params.memory_kib / 1024,
+                kdf_params.iterations,
+                kdf_params.parallelism,
+                key_file
+            )
+        }
+        KeySlotKind::Recipient { public_key, .. } => {
+            format!("recipient {}", public_key_to_hex(public_key))
+        }
+    };
+    println!(
+        "{} {} ({})",
+        success_style().paint(id.to_string()),
+        key_slot.label,
+        details
+    );
+}
+
+fn slot_list(path: Option<&Path>) -> Result<()> {
+    let file = match path {
+        Some(path) => LockedEncryptedFile::open_readonly(path)?,
+        None => open_passwords_file_readonly()?,
+    };
+    let key_slots = match file.key_slots() {
+        Some(key_slots) => key_slots,
+        None => return Err(ErrorKind::NoKeySlots.without_source_error()),
+    };
+    for (id, key_slot) in key_slots.iter().enumerate() {
+        print_key_slot(id, key_slot);
+    }
+    Ok(())
+}
+
+// unlocks the file with the password of any of its key slots
+fn unlock(path: Option<&Path>, key_file: Option<&Path>) -> Result<EncryptedFile> {
+    let (mut file, is_passwords_file) = open_file_for_writing(path)?;
+    if let Some(key_file) = key_file {
+        file.set_key_file(KeyFile::read(key_file)?);
+    }
+    let (unlocked_file, _) = unlock_file_with_password_for_writing(file, is_passwords_file)?;
+    Ok(unlocked_file)
+}
+
+fn prompt_user_for_new_password() -> Result<Secret<String>> {
+    let password = prompt_user_to_create_master_password("Enter the password of the new key slot: ")?;
+    if prompt_user_to_enter_password("Repeat the password of the new key slot: ")? != password {
+        return Err(ErrorKind::PasswordsDontMatch.without_source_error());
+    }
+    Ok(password)
+}
+
+// options of the key slot that `pwm slot add` adds
+pub struct NewKeySlot<'a> {
+    // the default label is used if None
+    pub label: Option<String>,
+    // use a generated recovery code as the password instead of prompting for one
+    pub recovery: bool,
+    // the key file that the new key slot requires along with its password
+    pub key_file: Option<&'a Path>,
+    pub kdf_options: KdfOptions,
+}
+
+// returns the id of the new key slot, and the recovery code if one was generated
+fn slot_add(
+    path: Option<&Path>,
+    key_file: Option<&Path>,
+    new_key_slot: NewKeySlot,
+) -> Result<(usize, Option<Secret<String>>)> {
+    let mut unlocked_file = unlock(path, key_file)?;
+    let new_key_file = match new_key_slot.key_file {
+        Some(new_key_file) => Some(KeyFile::read(new_key_file)?),
+        None => None,
+    };
+    let password = match new_key_slot.recovery {
+        true => generate_recovery_code(),
+        false => prompt_user_for_new_password()?,
+    };
+    let label = match (new_key_slot.label, new_key_slot.recovery) {
+        (Some(label), _) => label,
+        (None, true) => RECOVERY_KEY_SLOT_LABEL.to_string(),
+        (None, false) => DEFAULT_KEY_SLOT_LABEL.to_string(),
+    };
+    let id = unlocked_file.add_key_slot(
+        label,
+        &password,
+        new_key_slot.kdf_options.params()?,
+        new_key_file.as_ref(),
+    )?;
+    Ok((id, if new_key_slot.recovery { Some(password) } else { None }))
+}
+
+// returns false if the user didn't confirm revoking the key slot
+fn slot_revoke(
+    id: usize,
+    path: Option<&Path>,
+    key_file: Option<&Path>,
+    skip_confirmation: bool,
+) -> Result<bool> {
+    let mut unlocked_file = unlock(path, key_file)?;
+    let label = match unlocked_file.key_slots().get(id) {
+        Some(key_slot) => key_slot.label.clone(),
+        None => return Err(ErrorKind::NoSuchKeySlot.without_source_error()),
+    };
+    let unlocked_with_it = match unlocked_file.unlocked_key_slot() == Some(id) {
+        true => ", including the password that was just entered",
+        false => "",
+    };
+    if !skip_confirmation
+        && !prompt_user_to_confirm(&format!(
+            "Revoke key slot {} ({}), so that its password no longer unlocks the file{}?",
+            id, label, unlocked_with_it
+        ))?
+    {
+        return Ok(false);
+    }
+    unlocked_file.revoke_key_slot(id)?;
+    Ok(true)
+}
+
+pub fn slot_list_command(path: Option<&Path>) -> ExitCode {
+    match slot_list(path) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            print_error(e, file_prefix(path), &error_style());
+            ExitCode::FAILURE
+        }
+    }
+}
+
+pub fn slot_add_command(
+    path: Option<&Path>,
+    key_file: Option<&Path>,
+    new_key_slot: NewKeySlot,
+) -> ExitCode {
+    match slot_add(path, key_file, new_key_slot) {
+        Ok((id, recovery_code)) => {
+            println!(
+                "{}",
+                success_style().paint(format!("Key slot {} was successfully added", id))
+            );
+            if let Some(recovery_code) = recovery_code {
+                println!(
+                    "{} {}",
+                    warning_style().paint(
+                        "Write down the recovery code of the key slot, it's not shown again:"
+                    ),
+                    recovery_code.expose()
+                );
+            }
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            print_error(e, file_prefix(path), &error_style());
+            ExitCode::FAILURE
+        }
+    }
+}
+
+pub fn slot_revoke_command(
+    id: usize,
+    path: Option<&Path>,
+    key_file: Option<&Path>,
+    skip_confirmation: bool,
+) -> ExitCode {
+    match slot_revoke(id, path, key_file, skip_confirmation) {
+        Ok(true) => {
+            println!(
+                "{}",
+                success_style().paint(format!("Key slot {} was successfully revoked", id))
+            );
+            ExitCode::SUCCESS
+        }
+        Ok(false) => {
+            println!("{}", warning_style().paint("The key slot was not revoked"));
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            print_error(e, file_prefix(path), &error_style());
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -1,19 +1,33 @@
 use crate::styles::{error_style, success_style};
 use crate::input::prompt_user_to_unlock_file_with_password;
-use crate::locker::{LockedEncryptedFile,print_error,Result};
+use crate::locker::{KeyFile,LockedEncryptedFile,print_error,ErrorKind,Result};
+use std::path::Path;
+use std::process::ExitCode;
 
-fn unlock(path: &str) -> Result<()> {
-    let file = LockedEncryptedFile::open_write(path)?;
+fn unlock(path: &Path, key_file: Option<&Path>) -> Result<()> {
+    let mut file = LockedEncryptedFile::open_write(path)?;
+    if file.is_legacy() {
+        return Err(ErrorKind::LegacyFormat.without_source_error());
+    }
+    if let Some(key_file) = key_file {
+        file.set_key_file(KeyFile::read(key_file)?);
+    }
     let unlocked_file = prompt_user_to_unlock_file_with_password(file, "Enter password: ")?;
     unlocked_file.decrypt()?;
     Ok(())
 }
-pub fn unlock_command(path: &str) {
-    match unlock(path) {
-        Ok(()) => println!(
-            "{}",
-            success_style().paint("The target file was successfully unlocked")
-        ),
-        Err(e) => print_error(e, "target", &error_style()),
+pub fn unlock_command(path: &Path, key_file: Option<&Path>) -> ExitCode {
+    match unlock(path, key_file) {
+        Ok(()) => {
+            println!(
+                "{}",
+                success_style().paint("The target file was successfully unlocked")
+            );
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            print_error(e, "target", &error_style());
+            ExitCode::FAILURE
+        }
     }
 }
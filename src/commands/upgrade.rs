@@ -0,0 +1,65 @@
+use crate::commands::pwm::{migrate_passwords_file, open_passwords_file_for_writing};
+use crate::input::prompt_user_to_unlock_file_with_password;
+use crate::locker::{print_error, LockedEncryptedFile, Result, FORMAT_VERSION};
+use crate::styles::{error_style, success_style};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+// returns false if the file is already in the current format. the entries of copies of the
+// passwords file must be converted along with the headers
+fn upgrade(path: &Path, entries: bool) -> Result<bool> {
+    let file = LockedEncryptedFile::open_write(path)?;
+    if file.format_version() == FORMAT_VERSION {
+        return Ok(false);
+    }
+    let mut unlocked_file = prompt_user_to_unlock_file_with_password(file, "Enter password: ")?;
+    if entries {
+        migrate_passwords_file(&mut unlocked_file)?;
+    } else {
+        unlocked_file.rewrite_unversioned_content()?;
+    }
+    Ok(true)
+}
+// unlike other files, the content of the passwords file itself depends on the format version
+fn upgrade_passwords_file() -> Result<bool> {
+    let file = open_passwords_file_for_writing()?;
+    if file.format_version() == FORMAT_VERSION {
+        return Ok(false);
+    }
+    let mut unlocked_file =
+        prompt_user_to_unlock_file_with_password(file, "Enter master password: ")?;
+    migrate_passwords_file(&mut unlocked_file)?;
+    Ok(true)
+}
+pub fn upgrade_command(path: Option<PathBuf>, convert_entries_of_path: bool) -> ExitCode {
+    let (result, file_prefix) = match path {
+        Some(path) => (upgrade(&path, convert_entries_of_path), "target"),
+        None => (upgrade_passwords_file(), "passwords"),
+    };
+    match result {
+        Ok(was_upgraded) => {
+            if was_upgraded {
+                println!(
+                    "{}",
+                    success_style().paint(format!(
+                        "The {} file was successfully converted to the current format",
+                        file_prefix
+                    ))
+                );
+            } else {
+                println!(
+                    "{}",
+                    success_style().paint(format!(
+                        "The {} file is already in the current format",
+                        file_prefix
+                    ))
+                );
+            }
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            print_error(e, file_prefix, &error_style());
+            ExitCode::FAILURE
+        }
+    }
+}
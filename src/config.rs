@@ -0,0 +1,90 @@
+use crate::locker::{BackupRetention, CipherSuite, ErrorKind, Result};
+use std::path::PathBuf;
+
+// the config file consists of `key = value` lines, empty lines and lines starting with '#' are
+// ignored. for example:
+//
+//     # keep a backup of each of the last 30 days
+//     backup.keep_daily = 30
+pub struct Config {
+    pub backup_retention: BackupRetention,
+    // allows core dumps and attaching to pwm while files are unlocked, for debugging it
+    pub allow_debugging: bool,
+    // refuses to open a passwords file that other users can access instead of only warning
+    pub strict_permissions: bool,
+    // the key file that is used for files that require one when `--keyfile` isn't given
+    pub key_file: Option<PathBuf>,
+    // the identity that files and entries encrypted to the user are decrypted with, instead of the
+    // default one in the config directory
+    pub identity: Option<PathBuf>,
+    // the cipher suite that new files are encrypted with when `--cipher` isn't given
+    pub cipher_suite: CipherSuite,
+}
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            backup_retention: BackupRetention::DEFAULT,
+            allow_debugging: false,
+            strict_permissions: false,
+            key_file: None,
+            identity: None,
+            cipher_suite: CipherSuite::DEFAULT,
+        }
+    }
+}
+impl Config {
+    fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("pwm").join("config"))
+    }
+    pub fn identity_path(&self) -> Result<PathBuf> {
+        if let Some(path) = &self.identity {
+            return Ok(path.clone());
+        }
+        match dirs::config_dir() {
+            Some(dir) => Ok(dir.join("pwm").join("identity")),
+            None => Err(ErrorKind::HomeDir.without_source_error()),
+        }
+    }
+    // returns the default config if there is no config file
+    pub fn load() -> Result<Config> {
+        let path = match Config::path() {
+            Some(path) => path,
+            None => return Ok(Config::default()),
+        };
+        match std::fs::read_to_string(path) {
+            Ok(content) => Config::parse(&content),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Config::default()),
+            Err(err) => Err(ErrorKind::ReadConfig.with_source_error(err)),
+        }
+    }
+    fn parse(content: &str) -> Result<Config> {
+        let mut config = Config::default();
+        for (index, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid_line = || ErrorKind::InvalidConfig { line: index + 1 }.without_source_error();
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => return Err(invalid_line()),
+            };
+            let parse_amount = || value.parse::<usize>().map_err(|_| invalid_line());
+            let parse_flag = || value.parse::<bool>().map_err(|_| invalid_line());
+            match key {
+                "backup.keep_last" => config.backup_retention.keep_last = parse_amount()?,
+                "backup.keep_daily" => config.backup_retention.keep_daily = parse_amount()?,
+                "backup.keep_weekly" => config.backup_retention.keep_weekly = parse_amount()?,
+                "debugging.allow" => config.allow_debugging = parse_flag()?,
+                "permissions.strict" => config.strict_permissions = parse_flag()?,
+                "keyfile.path" => config.key_file = Some(PathBuf::from(value)),
+                "identity.path" => config.identity = Some(PathBuf::from(value)),
+                "cipher.suite" => {
+                    config.cipher_suite = value.parse().map_err(|_| invalid_line())?
+                }
+                _ => return Err(invalid_line()),
+            }
+        }
+        Ok(config)
+    }
+}
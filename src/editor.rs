@@ -0,0 +1,66 @@
+use crate::locker::{io_to_locker_error, ErrorKind, Result};
+use rand::{thread_rng, Rng};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+use std::process::Command;
+
+fn editor() -> String {
+    std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string())
+}
+
+// overwrite the temporary file before removing it so that the edited value
+// doesn't stay on the disk
+fn wipe_and_remove(path: &Path) {
+    if let Ok(mut file) = OpenOptions::new().write(true).open(path) {
+        if let Ok(metadata) = file.metadata() {
+            let _ = file.write_all(&vec![0u8; metadata.len() as usize]);
+            let _ = file.sync_all();
+        }
+    }
+    let _ = fs::remove_file(path);
+}
+
+fn run_editor(mut file: File, path: &Path, initial_value: &str) -> Result<String> {
+    io_to_locker_error(file.write_all(initial_value.as_bytes()), ErrorKind::Editor)?;
+    drop(file);
+
+    // run the editor through the shell so that editors with arguments (e.g. "code --wait") work
+    let status = io_to_locker_error(
+        Command::new("sh")
+            .arg("-c")
+            .arg(format!("{} \"$1\"", editor()))
+            .arg("sh")
+            .arg(path)
+            .status(),
+        ErrorKind::Editor,
+    )?;
+    if !status.success() {
+        return Err(ErrorKind::Editor.without_source_error());
+    }
+
+    let mut value = io_to_locker_error(fs::read_to_string(path), ErrorKind::Editor)?;
+    // most editors add a newline at the end of the file
+    if value.ends_with('\n') {
+        value.pop();
+    }
+    Ok(value)
+}
+
+pub fn edit_in_editor(initial_value: &str) -> Result<String> {
+    let path = std::env::temp_dir().join(format!("pwm-edit-{:016x}", thread_rng().gen::<u64>()));
+    let file = io_to_locker_error(
+        OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&path),
+        ErrorKind::Editor,
+    )?;
+    let result = run_editor(file, &path, initial_value);
+    wipe_and_remove(&path);
+    result
+}
@@ -0,0 +1,58 @@
+use crate::config::Config;
+use crate::locker::{io_to_locker_error, ErrorKind, Result};
+use crate::styles::warning_style;
+
+// a crash would dump the decrypted content into a core file, and a process that is attached to
+// this one can read it from its memory
+#[cfg(target_os = "linux")]
+fn disable_core_dumps_and_attaching() -> std::io::Result<()> {
+    let no_core_dumps = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    if unsafe { libc::setrlimit(libc::RLIMIT_CORE, &no_core_dumps) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    // also prevents processes of the same user from attaching to this one
+    if unsafe { libc::prctl(libc::PR_SET_DUMPABLE, 0, 0, 0, 0) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+#[cfg(not(target_os = "linux"))]
+fn disable_core_dumps_and_attaching() -> std::io::Result<()> {
+    Ok(())
+}
+
+// a tracer that attached before the process was made undumpable stays attached
+#[cfg(target_os = "linux")]
+fn is_traced() -> std::io::Result<bool> {
+    let status = std::fs::read_to_string("/proc/self/status")?;
+    Ok(status
+        .lines()
+        .filter_map(|line| line.strip_prefix("TracerPid:"))
+        .any(|tracer_pid| tracer_pid.trim() != "0"))
+}
+#[cfg(not(target_os = "linux"))]
+fn is_traced() -> std::io::Result<bool> {
+    Ok(false)
+}
+
+// must be called before anything is unlocked. setting `debugging.allow` in the config file allows
+// debugging pwm, in which case tracing only causes a warning
+pub fn harden_process() -> Result<()> {
+    let allow_debugging = Config::load()?.allow_debugging;
+    if !allow_debugging {
+        io_to_locker_error(disable_core_dumps_and_attaching(), ErrorKind::HardenProcess)?;
+    }
+    if io_to_locker_error(is_traced(), ErrorKind::HardenProcess)? {
+        if !allow_debugging {
+            return Err(ErrorKind::ProcessTraced.without_source_error());
+        }
+        println!(
+            "{}",
+            warning_style().paint("Warning: pwm is being traced by another process")
+        );
+    }
+    Ok(())
+}
@@ -1,22 +1,42 @@
 use crate::commands::pwm::master_password::check_master_password;
-use crate::locker::{ErrorKind, Result,EncryptedFile, LockedEncryptedFile};
-use std::io::{Stdout, Write};
-fn flush_stdout_and_read_password(stdout: &mut Stdout) -> std::io::Result<String> {
+use crate::config::Config;
+use crate::locker::{ErrorKind, Result,EncryptedFile, Identity, KeyFile, LockedEncryptedFile};
+use crate::hardening::harden_process;
+use crate::secret::Secret;
+use std::io::{BufRead, Stdout, Write};
+
+const MAX_PASSWORD_ATTEMPTS: usize = 3;
+fn flush_stdout_and_read_password(stdout: &mut Stdout) -> std::io::Result<Secret<String>> {
     stdout.flush()?;
-    rpassword::read_password()
+    rpassword::read_password().map(Secret::new)
 }
-pub fn prompt_user_to_unlock_file_with_password(
-    mut file: LockedEncryptedFile,
+// returns the password once it matches the file, without unlocking it. if any of the key slots of
+// the file requires a key file and none was set, the one in the config file is used
+pub fn prompt_user_for_file_password(
+    file: &mut LockedEncryptedFile,
     prompt: &str,
-) -> Result<EncryptedFile> {
+) -> Result<Secret<String>> {
+    harden_process()?;
+    if !file.has_password_key_slots() {
+        return Err(ErrorKind::NotARecipient.without_source_error());
+    }
+    if file.uses_key_file() && !file.has_key_file() {
+        match Config::load()?.key_file {
+            Some(path) => file.set_key_file(KeyFile::read(path)?),
+            None if file.requires_key_file() => {
+                return Err(ErrorKind::KeyFileRequired.without_source_error())
+            }
+            None => {}
+        }
+    }
     let mut stdout = std::io::stdout();
-    loop {
+    for _ in 0..MAX_PASSWORD_ATTEMPTS {
         print!("{}", prompt);
         match flush_stdout_and_read_password(&mut stdout) {
-            Err(e) => break Err(ErrorKind::PromptPasswordIOError.with_source_error(e)),
+            Err(e) => return Err(ErrorKind::PromptPasswordIOError.with_source_error(e)),
             Ok(password) => {
                 if file.test_key(&password) {
-                    break file.unlock(&password);
+                    return Ok(password);
                 } else {
                     println!("Wrong password");
                     println!();
@@ -24,9 +44,34 @@ pub fn prompt_user_to_unlock_file_with_password(
             }
         }
     }
+    Err(ErrorKind::WrongPassword.without_source_error())
+}
+// the identity of the user, if the file is encrypted to it
+fn load_identity_of_recipient(file: &LockedEncryptedFile) -> Result<Option<Identity>> {
+    if file.key_slots().is_none() {
+        return Ok(None);
+    }
+    let path = Config::load()?.identity_path()?;
+    if !path.exists() {
+        return Ok(None);
+    }
+    let identity = Identity::read(path)?;
+    Ok(Some(identity).filter(|identity| file.is_encrypted_to(&identity.public_key())))
+}
+// files that are encrypted to the identity of the user are unlocked with it instead of a password
+pub fn prompt_user_to_unlock_file_with_password(
+    mut file: LockedEncryptedFile,
+    prompt: &str,
+) -> Result<EncryptedFile> {
+    if let Some(identity) = load_identity_of_recipient(&file)? {
+        harden_process()?;
+        return file.unlock_with_identity(&identity);
+    }
+    let password = prompt_user_for_file_password(&mut file, prompt)?;
+    file.unlock(&password)
 }
 
-pub fn prompt_user_to_create_master_password(prompt: &str) -> Result<String> {
+pub fn prompt_user_to_create_master_password(prompt: &str) -> Result<Secret<String>> {
     let mut stdout = std::io::stdout();
     loop {
         print!("{}", prompt);
@@ -42,3 +87,23 @@ pub fn prompt_user_to_create_master_password(prompt: &str) -> Result<String> {
         }
     }
 }
+
+pub fn prompt_user_to_enter_password(prompt: &str) -> Result<Secret<String>> {
+    print!("{}", prompt);
+    match flush_stdout_and_read_password(&mut std::io::stdout()) {
+        Ok(password) => Ok(password),
+        Err(e) => Err(ErrorKind::PromptPasswordIOError.with_source_error(e)),
+    }
+}
+
+pub fn prompt_user_to_confirm(prompt: &str) -> Result<bool> {
+    print!("{} [y/N] ", prompt);
+    let mut answer = String::new();
+    let read_result = std::io::stdout()
+        .flush()
+        .and_then(|()| std::io::stdin().lock().read_line(&mut answer));
+    match read_result {
+        Ok(_) => Ok(matches!(answer.trim(), "y" | "Y" | "yes")),
+        Err(e) => Err(ErrorKind::PromptConfirmationIOError.with_source_error(e)),
+    }
+}
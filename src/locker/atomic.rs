@@ -0,0 +1,95 @@
+use crate::locker::{
+    io_to_locker_error, lock_file, ErrorKind, LockMode, MutableFile, Result, UnixFile, UnixFileFlag,
+    PRIVATE_FILE_MODE,
+};
+use rand::{thread_rng, RngCore};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+
+fn is_immutable(file: &File) -> bool {
+    cfg!(unix)
+        && file
+            .get_unix_flags()
+            .map(|flags| flags.is_flag_set(UnixFileFlag::Immutable))
+            .unwrap_or(false)
+}
+
+fn parent_dir(path: &Path) -> &Path {
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    }
+}
+
+// the temporary file must be in the same directory as the original, since a rename is only atomic
+// within a single filesystem
+fn create_temp_file(path: &Path) -> Result<(PathBuf, File)> {
+    let dir = parent_dir(path);
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let temp_path = dir.join(format!(".{}.{:016x}.tmp", file_name, thread_rng().next_u64()));
+    let temp_file = io_to_locker_error(
+        OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .mode(PRIVATE_FILE_MODE)
+            .open(&temp_path),
+        ErrorKind::WriteFile,
+    )?;
+    Ok((temp_path, temp_file))
+}
+
+fn write_temp_file(temp_file: &mut File, original: &File, content: &[&[u8]]) -> Result<()> {
+    for part in content {
+        io_to_locker_error(temp_file.write_all(part), ErrorKind::WriteFile)?;
+    }
+    let permissions = io_to_locker_error(original.metadata(), ErrorKind::ReadFile)?.permissions();
+    io_to_locker_error(temp_file.set_permissions(permissions), ErrorKind::WriteFile)?;
+    io_to_locker_error(temp_file.sync_all(), ErrorKind::WriteFile)
+}
+
+// replaces the content of the file at the given path by writing the new content to a temporary
+// file and renaming it over the original, so that a crash or a full disk midway leaves either the
+// old or the new content, but never a mix of both. returns the new file, opened for reading and
+// writing. if the original file is immutable, the new one is made immutable as well.
+pub fn replace_file(path: &Path, original: &mut File, content: &[&[u8]]) -> Result<File> {
+    let (temp_path, mut temp_file) = create_temp_file(path)?;
+    // lock the new file before it replaces the original, so that other processes waiting for the
+    // lock on the original keep waiting once they find out that it was replaced
+    let write_result = lock_file(&temp_file, LockMode::Exclusive)
+        .and_then(|()| write_temp_file(&mut temp_file, original, content));
+    if let Err(err) = write_result {
+        let _ = fs::remove_file(&temp_path);
+        return Err(err);
+    }
+
+    let was_immutable = is_immutable(original);
+    if was_immutable {
+        if let Err(err) = original.make_mutable() {
+            let _ = fs::remove_file(&temp_path);
+            return Err(err);
+        }
+    }
+    if let Err(err) = fs::rename(&temp_path, path) {
+        let _ = fs::remove_file(&temp_path);
+        // the flag was cleared just above, so restore it, but report why the rename failed rather
+        // than why restoring the flag did
+        if was_immutable {
+            let _ = original.make_immutable();
+        }
+        return Err(ErrorKind::ReplaceFile.with_source_error(err));
+    }
+    if was_immutable {
+        temp_file.make_immutable()?;
+    }
+
+    // make sure that the rename itself is persisted
+    let dir_sync_result = File::open(parent_dir(path)).and_then(|dir| dir.sync_all());
+    io_to_locker_error(dir_sync_result, ErrorKind::WriteFile)?;
+    Ok(temp_file)
+}
@@ -0,0 +1,191 @@
+use crate::locker::{
+    io_to_locker_error, open_locked, replace_file, ErrorKind, LockMode, Result, PRIVATE_DIR_MODE,
+    PRIVATE_FILE_MODE,
+};
+use std::collections::HashSet;
+use std::fs::{self, DirBuilder, File, OpenOptions};
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const SECONDS_IN_DAY: u64 = 24 * 60 * 60;
+const SECONDS_IN_WEEK: u64 = 7 * SECONDS_IN_DAY;
+const BACKUP_EXTENSION: &str = "backup";
+
+// which backups are kept when a new one is made, a backup is kept if any of the rules keeps it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BackupRetention {
+    // the most recent backups
+    pub keep_last: usize,
+    // the most recent backup of each of the last days that have backups
+    pub keep_daily: usize,
+    // the most recent backup of each of the last weeks that have backups
+    pub keep_weekly: usize,
+}
+impl BackupRetention {
+    pub const DEFAULT: BackupRetention = BackupRetention {
+        keep_last: 10,
+        keep_daily: 7,
+        keep_weekly: 4,
+    };
+    fn keeps_any(&self) -> bool {
+        self.keep_last > 0 || self.keep_daily > 0 || self.keep_weekly > 0
+    }
+    // the backups must be sorted from the newest to the oldest
+    fn ids_to_keep(&self, backups: &[Backup]) -> HashSet<u64> {
+        let mut kept: HashSet<u64> = backups.iter().take(self.keep_last).map(|b| b.id).collect();
+        let mut keep_one_per_period = |period_ms: u64, amount: usize| {
+            let mut last_period = None;
+            let mut kept_periods = 0;
+            for backup in backups {
+                if kept_periods == amount {
+                    break;
+                }
+                let period = backup.id / period_ms;
+                if last_period != Some(period) {
+                    last_period = Some(period);
+                    kept_periods += 1;
+                    kept.insert(backup.id);
+                }
+            }
+        };
+        keep_one_per_period(SECONDS_IN_DAY * 1000, self.keep_daily);
+        keep_one_per_period(SECONDS_IN_WEEK * 1000, self.keep_weekly);
+        kept
+    }
+}
+
+pub struct Backup {
+    // the time the backup was made at, in milliseconds since the unix epoch
+    pub id: u64,
+    pub path: PathBuf,
+}
+impl Backup {
+    pub fn created_at(&self) -> u64 {
+        self.id / 1000
+    }
+}
+
+// a directory of copies of the encrypted file, made before each time it's written
+pub struct Backups {
+    dir: PathBuf,
+    retention: BackupRetention,
+}
+impl Backups {
+    pub fn new(dir: PathBuf, retention: BackupRetention) -> Backups {
+        Backups { dir, retention }
+    }
+    // returns the backups sorted from the newest to the oldest
+    pub fn list(&self) -> Result<Vec<Backup>> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(ErrorKind::Backup.with_source_error(err)),
+        };
+        let mut backups = Vec::new();
+        for entry in entries {
+            let path = io_to_locker_error(entry, ErrorKind::Backup)?.path();
+            if path.extension().and_then(|extension| extension.to_str()) != Some(BACKUP_EXTENSION) {
+                continue;
+            }
+            let id = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| stem.parse().ok());
+            if let Some(id) = id {
+                backups.push(Backup { id, path });
+            }
+        }
+        backups.sort_by_key(|backup| std::cmp::Reverse(backup.id));
+        Ok(backups)
+    }
+    pub fn find(&self, id: u64) -> Result<Backup> {
+        match self.list()?.into_iter().find(|backup| backup.id == id) {
+            Some(backup) => Ok(backup),
+            None => Err(ErrorKind::NoSuchBackup.without_source_error()),
+        }
+    }
+    // copies the file at the given path to a new backup, and removes the backups that are no
+    // longer kept
+    pub fn save(&self, path: &Path) -> Result<()> {
+        self.save_keeping(path, None)
+    }
+    // like save, but the backup with the given id isn't removed even if it's no longer kept
+    fn save_keeping(&self, path: &Path, kept_id: Option<u64>) -> Result<()> {
+        if !self.retention.keeps_any() {
+            return Ok(());
+        }
+        io_to_locker_error(
+            DirBuilder::new()
+                .recursive(true)
+                .mode(PRIVATE_DIR_MODE)
+                .create(&self.dir),
+            ErrorKind::Backup,
+        )?;
+        // the directory might have been created with other permissions
+        io_to_locker_error(
+            fs::set_permissions(&self.dir, fs::Permissions::from_mode(PRIVATE_DIR_MODE)),
+            ErrorKind::Backup,
+        )?;
+        let mut id = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis() as u64)
+            .unwrap_or(0);
+        // never overwrite an existing backup, even if two were made in the same millisecond
+        while self.backup_path(id).exists() {
+            id += 1;
+        }
+        self.copy_to_backup(path, id)?;
+        self.prune(kept_id)
+    }
+    fn copy_to_backup(&self, path: &Path, id: u64) -> Result<()> {
+        let mut file = io_to_locker_error(File::open(path), ErrorKind::Backup)?;
+        let mut backup = io_to_locker_error(
+            OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .mode(PRIVATE_FILE_MODE)
+                .open(self.backup_path(id)),
+            ErrorKind::Backup,
+        )?;
+        io_to_locker_error(std::io::copy(&mut file, &mut backup), ErrorKind::Backup)?;
+        io_to_locker_error(backup.sync_all(), ErrorKind::Backup)
+    }
+    fn backup_path(&self, id: u64) -> PathBuf {
+        self.dir.join(format!("{}.{}", id, BACKUP_EXTENSION))
+    }
+    fn prune(&self, kept_id: Option<u64>) -> Result<()> {
+        let backups = self.list()?;
+        let mut kept = self.retention.ids_to_keep(&backups);
+        kept.extend(kept_id);
+        for backup in backups {
+            if !kept.contains(&backup.id) {
+                io_to_locker_error(fs::remove_file(&backup.path), ErrorKind::Backup)?;
+            }
+        }
+        Ok(())
+    }
+    // replaces the file at the given path with the backup, after backing up the file itself so
+    // that the restore can be undone. the file doesn't have to be a valid encrypted file, so that
+    // a corrupted file can be restored. the restored backup is kept even if backing up the file
+    // would remove it, since it's usually the oldest one
+    pub fn restore(&self, backup: &Backup, path: &Path) -> Result<()> {
+        let content = io_to_locker_error(fs::read(&backup.path), ErrorKind::Backup)?;
+        let mut file = open_locked(
+            path,
+            OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .mode(PRIVATE_FILE_MODE),
+            LockMode::Exclusive,
+        )?;
+        let path = io_to_locker_error(path.canonicalize(), ErrorKind::OpenFile)?;
+        if io_to_locker_error(file.metadata(), ErrorKind::ReadFile)?.len() > 0 {
+            self.save_keeping(&path, Some(backup.id))?;
+        }
+        replace_file(&path, &mut file, &[&content])?;
+        Ok(())
+    }
+}
@@ -0,0 +1,51 @@
+use std::str::FromStr;
+
+const XCHACHA20_POLY1305_ID: u8 = 0;
+const AES_256_GCM_SIV_ID: u8 = 1;
+
+// the aead that the content of a file is encrypted with, whose id is stored in the headers. the
+// key slots, hidden vaults and shared files are always encrypted with the default suite
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CipherSuite {
+    XChaCha20Poly1305,
+    // resistant to nonce misuse, and accelerated by the aes instructions of most cpus
+    Aes256GcmSiv,
+}
+impl CipherSuite {
+    pub const DEFAULT: CipherSuite = CipherSuite::XChaCha20Poly1305;
+    pub fn id(self) -> u8 {
+        match self {
+            CipherSuite::XChaCha20Poly1305 => XCHACHA20_POLY1305_ID,
+            CipherSuite::Aes256GcmSiv => AES_256_GCM_SIV_ID,
+        }
+    }
+    // None for the ids of suites that were added by a newer version
+    pub fn from_id(id: u8) -> Option<CipherSuite> {
+        match id {
+            XCHACHA20_POLY1305_ID => Some(CipherSuite::XChaCha20Poly1305),
+            AES_256_GCM_SIV_ID => Some(CipherSuite::Aes256GcmSiv),
+            _ => None,
+        }
+    }
+    pub fn name(self) -> &'static str {
+        match self {
+            CipherSuite::XChaCha20Poly1305 => "xchacha20-poly1305",
+            CipherSuite::Aes256GcmSiv => "aes-256-gcm-siv",
+        }
+    }
+}
+impl FromStr for CipherSuite {
+    type Err = String;
+    fn from_str(s: &str) -> std::result::Result<CipherSuite, Self::Err> {
+        [CipherSuite::XChaCha20Poly1305, CipherSuite::Aes256GcmSiv]
+            .iter()
+            .copied()
+            .find(|cipher_suite| cipher_suite.name() == s)
+            .ok_or_else(|| {
+                format!(
+                    "unknown cipher suite `{}`, expected xchacha20-poly1305 or aes-256-gcm-siv",
+                    s
+                )
+            })
+    }
+}
@@ -1,8 +1,7 @@
-use crate::{locker::{ENCRYPTION_HEADERS_SIZE, EncryptedFile, ErrorKind, Result, io_to_locker_error}};
-use rand::{thread_rng, RngCore};
-use sha3::Digest;
+use crate::locker::{EncryptedFile, ErrorKind, Result, Sealed, SealedReader};
+use crate::secret::Secret;
+use crate::vec_io::VecReader;
 use std::fs::File;
-use std::io::Write;
 
 pub struct EncryptedFileReader<'a> {
     file: &'a mut EncryptedFile,
@@ -16,159 +15,135 @@ impl EncryptedFileReader<'_> {
     pub fn eof(&self) -> bool {
         self.file.reader.eof()
     }
+    // moves to the given offset in the decrypted content, or to its end if it's past it
+    pub fn seek(&mut self, offset: usize) {
+        self.file.reader.set_position(offset)
+    }
+}
+// reads from decrypted content, failing with the offset that it failed at if the content is too
+// short. shared by the readers of the whole content and of its sealed parts
+pub(super) fn read_exact(reader: &mut VecReader, buf: &mut [u8]) -> Result<()> {
+    let offset = reader.position();
+    match reader.read_exact(buf) {
+        Ok(_) => Ok(()),
+        Err(_) => Err(ErrorKind::CorruptedFile { offset }.without_source_error()),
+    }
 }
+pub(super) fn read_bytes(reader: &mut VecReader, amount: u64) -> Result<Vec<u8>> {
+    if amount > reader.rest().len() as u64 {
+        return Err(ErrorKind::CorruptedFile {
+            offset: reader.position(),
+        }
+        .without_source_error());
+    }
+    let mut res = vec![0u8; amount as usize];
+    read_exact(reader, &mut res)?;
+    Ok(res)
+}
+pub(super) fn read_until(reader: &mut VecReader, terminator: u8) -> Result<Vec<u8>> {
+    let offset = reader.position();
+    let length = match reader.rest().iter().position(|byte| *byte == terminator) {
+        Some(length) => length,
+        None => return Err(ErrorKind::CorruptedFile { offset }.without_source_error()),
+    };
+    let res = reader.rest()[..length].to_vec();
+    reader.consume(length + 1);
+    Ok(res)
+}
+
 impl<'a> crate::locker::EncryptedRead for EncryptedFileReader<'a> {
     fn read_exact(&mut self, buf: &mut [u8]) -> Result<()> {
-        match self.file.reader.read_exact(buf) {
-            Ok(_) => Ok(()),
-            Err(_) => Err(ErrorKind::CorruptedFile.without_source_error()),
-        }
+        read_exact(&mut self.file.reader, buf)
     }
-    fn read_until(&mut self, terminator: u8) -> Vec<u8> {
-        let mut length = 0usize;
-        let mut res = Vec::new();
-        // used the extra scop so that rest, which is a reference to self.file.reader will
-        // be dropped, otherwise self.file.reader couldn't be used later on
-        {
-            let rest = self.file.reader.rest();
-            while rest[length] != terminator && length < rest.len() {
-                length += 1;
-            }
-            res.extend_from_slice(&rest[..length]);
-        }
-        self.file.reader.consume(length + 1);
-        res
+    fn read_bytes(&mut self, amount: u64) -> Result<Vec<u8>> {
+        read_bytes(&mut self.file.reader, amount)
+    }
+    fn read_until(&mut self, terminator: u8) -> Result<Vec<u8>> {
+        read_until(&mut self.file.reader, terminator)
+    }
+    fn offset(&self) -> usize {
+        self.file.reader.position()
+    }
+    fn peek(&self) -> Option<u8> {
+        self.file.reader.rest().first().copied()
+    }
+    fn format_version(&self) -> u16 {
+        self.file.format_version()
+    }
+}
+impl<'a> crate::locker::EncryptedUnseal for EncryptedFileReader<'a> {
+    fn unseal(&self, sealed: &Sealed, associated_data: &[u8]) -> Result<SealedReader> {
+        let reader = self.file.encryptor.unseal(sealed, associated_data)?;
+        Ok(SealedReader::new(reader, self.file.format_version()))
     }
 }
 
 pub struct EncryptedFileWriter<'a> {
     file: &'a mut EncryptedFile,
-    buffer: Vec<u8>,
+    // the content that is written, which is wiped if it's dropped before it's flushed
+    buffer: Secret<Vec<u8>>,
 }
 impl<'a> EncryptedFileWriter<'a> {
     pub fn new(file: &'a mut EncryptedFile) -> EncryptedFileWriter<'a> {
-        // regenerate a new random nonce - never reuse the same keystream!!
-        thread_rng().fill_bytes(&mut file.headers.nonce);
-        file.encryptor.reset_with_nonce(&file.headers.nonce);
         EncryptedFileWriter {
             file,
-            buffer: Vec::new(),
+            buffer: Secret::new(Vec::new()),
         }
     }
-    fn encrypt_and_write_buffer(&mut self) -> Result<()> {
-        self.file.encryptor.apply(&mut self.buffer)?;
-        io_to_locker_error(self.file.file.write_all(&self.buffer), ErrorKind::WriteFile)
-    }
 }
 impl<'a> crate::locker::EncryptedWrite for EncryptedFileWriter<'a> {
-    fn write(&mut self, byte: u8) -> &mut Self {
-        // add the bytes to the buffer
-        self.buffer.push(byte);
-        self
-    }
     fn write_all(&mut self, buf: &[u8]) -> &mut Self {
         // add the bytes to the buffer
-        self.buffer.extend(buf);
+        self.buffer.extend_from_slice(buf);
         self
     }
 }
+impl<'a> crate::locker::EncryptedSeal for EncryptedFileWriter<'a> {
+    fn seal(&self, associated_data: &[u8], content: &[u8]) -> Result<Sealed> {
+        self.file.encryptor.seal(associated_data, content)
+    }
+}
 impl<'a> crate::locker::EncryptedFlush for EncryptedFileWriter<'a> {
     fn flush(mut self) -> Result<()> {
-        // update the hmac hasher
-        self.file.hasher.update(&self.buffer);
-        self.file
-            .headers
-            .hmac
-            .as_mut()
-            .copy_from_slice(&self.file.hasher.finalize_reset());
-        // rewrite the hmac
-        self.file.seek_file(0)?;
-        self.file.write_hmac_and_nonce()?;
-
-        // write the new content
-        self.file.seek_file(ENCRYPTION_HEADERS_SIZE as u64)?;
-        self.encrypt_and_write_buffer()?;
-
-        // if the length of the original content of the file (without the headers - thus rest),
-        // is bigger then the new content's length
-        if self.file.reader.rest().len() > self.buffer.len() {
-            io_to_locker_error(
-                self.file.file.set_len(ENCRYPTION_HEADERS_SIZE as u64 + self.buffer.len() as u64),
-                ErrorKind::TruncateFile,
-            )?;
-        }
-        Ok(())
+        // the buffer replaces the whole content of the file
+        self.file.write_content(self.buffer.take())
     }
 }
 
 pub struct EncryptedFileAppender<'a> {
     file: &'a mut EncryptedFile,
-    buffer: Vec<u8>,
+    // the content that is written, which is wiped if it's dropped before it's flushed
+    buffer: Secret<Vec<u8>>,
 }
 impl<'a> EncryptedFileAppender<'a> {
     pub fn new(file: &'a mut EncryptedFile) -> EncryptedFileAppender<'a> {
-        // regenerate a new random nonce - never reuse the same keystream!!
-        thread_rng().fill_bytes(&mut file.headers.nonce);
-        file.encryptor.reset_with_nonce(&file.headers.nonce);
-
-        // update the hmac hasher with the original content of the file,
-        // and then re-update it everytime we append to get the full hmac
-        file.hasher.update(file.reader.rest());
         EncryptedFileAppender {
             file,
-            buffer: Vec::new(),
+            buffer: Secret::new(Vec::new()),
         }
     }
-    fn encrypt_and_write_buffer(&mut self) -> Result<()> {
-        self.file.encryptor.apply(&mut self.buffer)?;
-        io_to_locker_error(self.file.file.write_all(&self.buffer), ErrorKind::WriteFile)
-    }
-    fn write_reader_rest(&mut self)->Result<()>{
-        io_to_locker_error(self.file.file.write_all(self.file.reader.rest()),ErrorKind::WriteFile)
-    }
 }
 impl<'a> crate::locker::EncryptedWrite for EncryptedFileAppender<'a> {
-    fn write(&mut self, byte: u8) -> &mut Self {
-        // add the bytes to the buffer
-        self.buffer.push(byte);
-        self
-    }
     fn write_all(&mut self, buf: &[u8]) -> &mut Self {
         // add the bytes to the buffer
-        self.buffer.extend(buf);
+        self.buffer.extend_from_slice(buf);
         self
     }
 }
+impl<'a> crate::locker::EncryptedSeal for EncryptedFileAppender<'a> {
+    fn seal(&self, associated_data: &[u8], content: &[u8]) -> Result<Sealed> {
+        self.file.encryptor.seal(associated_data, content)
+    }
+}
 impl<'a> crate::locker::EncryptedFlush for EncryptedFileAppender<'a> {
-    fn flush(mut self) -> Result<()> {
-        // reencrypt the original content with the new nonce
-        self.file.encryptor.apply(self.file.reader.rest_mut())?;
-
-        // update the hmac hasher
-        self.file.hasher.update(&self.buffer);
-        self.file
-            .headers
-            .hmac
-            .as_mut()
-            .copy_from_slice(&self.file.hasher.finalize_reset());
-
-        // rewrite the hmac
-        self.file.seek_file(0)?;
-        self.file.write_hmac_and_nonce()?;
-
-        //  === append the new content ===
-
-        // seek to the start of the content
-        self.file
-            .seek_file(ENCRYPTION_HEADERS_SIZE as u64)?;
-
-        // rewrite the original content after it has been encrypted with the new nonce
-        self.write_reader_rest()?;
-
-        // write the appended content
-        self.encrypt_and_write_buffer()?;
-
-        Ok(())
+    fn flush(self) -> Result<()> {
+        // the whole content is reencrypted with a new nonce, followed by the appended content. the
+        // content is allocated at once so that it isn't left behind in memory by a reallocation
+        let current_content = self.file.reader.buffer();
+        let mut content = Vec::with_capacity(current_content.len() + self.buffer.len());
+        content.extend_from_slice(current_content);
+        content.extend_from_slice(&self.buffer);
+        self.file.write_content(content)
     }
 }
 
@@ -208,31 +183,3 @@ impl<'a> InnerFile for EncryptedFileAppender<'a> {
         &mut self.file.file
     }
 }
-pub trait InnerEncryptedFile {
-    fn inner_encrypted_file(&self) -> &EncryptedFile;
-    fn inner_encrypted_file_mut(&mut self) -> &mut EncryptedFile;
-}
-impl<'a> InnerEncryptedFile for EncryptedFileReader<'a> {
-    fn inner_encrypted_file(&self) -> &EncryptedFile {
-        &self.file
-    }
-    fn inner_encrypted_file_mut(&mut self) -> &mut EncryptedFile {
-        &mut self.file
-    }
-}
-impl<'a> InnerEncryptedFile for EncryptedFileWriter<'a> {
-    fn inner_encrypted_file(&self) -> &EncryptedFile {
-        &self.file
-    }
-    fn inner_encrypted_file_mut(&mut self) -> &mut EncryptedFile {
-        &mut self.file
-    }
-}
-impl<'a> InnerEncryptedFile for EncryptedFileAppender<'a> {
-    fn inner_encrypted_file(&self) -> &EncryptedFile {
-        &self.file
-    }
-    fn inner_encrypted_file_mut(&mut self) -> &mut EncryptedFile {
-        &mut self.file
-    }
-}
@@ -1,58 +1,116 @@
 mod io;
-pub use self::io::*;
+mod sealed;
+pub use self::{io::*, sealed::*};
 
-use crate::{
-    locker::{
-        io_to_locker_error, to_locker_error, EncryptionHeaders, ErrorKind, MutableFile, Result,
-        ENCRYPTION_HEADERS_SIZE,
-    },
-    passwords::Password,
-};
-use crate::{
-    passwords::{PasswordReader, PasswordWriter},
-    vec_io::VecReader,
-};
-use chacha20::{
-    cipher::NewStreamCipher, cipher::SyncStreamCipher, cipher::SyncStreamCipherSeek, ChaCha20,
-    Nonce,
+use crate::locker::{
+    io_to_locker_error, open_locked, random_hidden_region, Backups, CipherSuite, HiddenVault, Identity, RecipientKey, read_format_version, replace_file, LockMode, EncryptionHeaders, DerivedKeyHeaders, ErrorKind, KdfParams, KeyDerivation, KeyFile, KeyShare, KeySlot, split_key,
+    LegacyHeaders, LegacyKey, DERIVED_KEY_SIZE, MutableFile, Result, FORMAT_HEADER_SIZE, POLY1305_TAG_SIZE, new_data_key,
+    CONTENT_LAYOUT_FORMAT_VERSION, FORMAT_VERSION, HIDDEN_REGION_FORMAT_VERSION, HIDDEN_REGION_SIZE, KEY_SLOTS_FORMAT_VERSION, DEFAULT_KEY_SLOT_LABEL, MAX_KEY_SLOTS, PRIVATE_FILE_MODE, UNVERSIONED_FORMAT_VERSION, verify_private_file,
 };
+use chacha20::cipher::{NewStreamCipher, SyncStreamCipher, SyncStreamCipherSeek};
+use chacha20::XChaCha20;
+use crate::secret::Secret;
+use chacha20poly1305::XNonce;
+use crate::vec_io::VecReader;
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{Key, XChaCha20Poly1305};
+use aes_gcm_siv::Aes256GcmSiv;
 use rand::{thread_rng, RngCore};
-use sha3::{Digest, Sha3_256, Sha3_512};
 use std::fs::{File, OpenOptions};
-use std::io::{Read, Seek, SeekFrom, Write};
+use std::io::Read;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
 
-use super::EncryptedFlush;
+// the first block of the keystream is used for the poly1305 key, the content is encrypted with the
+// rest of it
+const CHACHA20_BLOCK_SIZE: u64 = 64;
+// the nonces in the file are the size of xchacha20 nonces, aes-256-gcm-siv uses their prefix
+const AES_GCM_SIV_NONCE_SIZE: usize = 12;
+
+// the expanded key schedule of aes is much larger than the key of xchacha20, so it's boxed
+enum Cipher {
+    XChaCha20Poly1305(XChaCha20Poly1305),
+    Aes256GcmSiv(Box<Aes256GcmSiv>),
+}
 
-type Sha256Digest = generic_array::GenericArray<u8, <Sha3_256 as Digest>::OutputSize>;
 pub struct Encryptor {
-    chacha: ChaCha20,
-    hashed_key: Sha256Digest,
+    cipher: Cipher,
+    key: Secret<[u8; DERIVED_KEY_SIZE]>,
 }
 impl Encryptor {
-    pub fn new<B: AsRef<[u8]>>(key: B, nonce: &Nonce) -> Encryptor {
-        let mut key_hasher = Sha3_256::new();
-        key_hasher.update(key);
-        let key = key_hasher.finalize();
+    pub fn new<B: AsRef<[u8]>>(key: B) -> Encryptor {
+        Encryptor::with_cipher_suite(key, CipherSuite::DEFAULT)
+    }
+    pub fn with_cipher_suite<B: AsRef<[u8]>>(key: B, cipher_suite: CipherSuite) -> Encryptor {
+        let mut key_bytes = Secret::new([0u8; DERIVED_KEY_SIZE]);
+        key_bytes.expose_mut().copy_from_slice(key.as_ref());
+        let cipher = match cipher_suite {
+            CipherSuite::XChaCha20Poly1305 => {
+                Cipher::XChaCha20Poly1305(XChaCha20Poly1305::new(Key::from_slice(key.as_ref())))
+            }
+            CipherSuite::Aes256GcmSiv => Cipher::Aes256GcmSiv(Box::new(Aes256GcmSiv::new(
+                aes_gcm_siv::Key::<Aes256GcmSiv>::from_slice(key.as_ref()),
+            ))),
+        };
         Encryptor {
-            chacha: ChaCha20::new(&key, nonce),
-            hashed_key: key,
+            cipher,
+            key: key_bytes,
         }
     }
-    pub fn apply(&mut self, buf: &mut [u8]) -> Result<()> {
-        match self.chacha.try_apply_keystream(buf) {
-            Ok(_) => Ok(()),
+    pub fn cipher_suite(&self) -> CipherSuite {
+        match self.cipher {
+            Cipher::XChaCha20Poly1305(_) => CipherSuite::XChaCha20Poly1305,
+            Cipher::Aes256GcmSiv(_) => CipherSuite::Aes256GcmSiv,
+        }
+    }
+    // encrypts the content and appends its tag, authenticating the associated data along with it
+    pub fn encrypt(&self, nonce: &XNonce, associated_data: &[u8], content: &[u8]) -> Result<Vec<u8>> {
+        let payload = Payload {
+            msg: content,
+            aad: associated_data,
+        };
+        let encrypted = match &self.cipher {
+            Cipher::XChaCha20Poly1305(cipher) => cipher.encrypt(nonce, payload),
+            Cipher::Aes256GcmSiv(cipher) => cipher.encrypt(
+                aes_gcm_siv::Nonce::from_slice(&nonce[..AES_GCM_SIV_NONCE_SIZE]),
+                payload,
+            ),
+        };
+        match encrypted {
+            Ok(encrypted) => Ok(encrypted),
             Err(_) => Err(ErrorKind::EncryptionError.without_source_error()),
         }
     }
-    pub fn advance(&mut self, amount: u64) -> Result<()> {
-        let cur: u64 = to_locker_error(self.chacha.try_current_pos(), ErrorKind::EncryptionError)?;
-        to_locker_error(
-            self.chacha.try_seek(cur + amount),
-            ErrorKind::EncryptionError,
-        )
+    pub fn decrypt(&self, nonce: &XNonce, associated_data: &[u8], encrypted: &[u8]) -> Result<Vec<u8>> {
+        let payload = Payload {
+            msg: encrypted,
+            aad: associated_data,
+        };
+        let content = match &self.cipher {
+            Cipher::XChaCha20Poly1305(cipher) => cipher.decrypt(nonce, payload),
+            Cipher::Aes256GcmSiv(cipher) => cipher.decrypt(
+                aes_gcm_siv::Nonce::from_slice(&nonce[..AES_GCM_SIV_NONCE_SIZE]),
+                payload,
+            ),
+        };
+        match content {
+            Ok(content) => Ok(content),
+            Err(_) => Err(ErrorKind::MacError.without_source_error()),
+        }
     }
-    pub fn reset_with_nonce(&mut self, nonce: &Nonce) {
-        self.chacha = ChaCha20::new(&self.hashed_key, nonce)
+    // decrypts the content without verifying its tag, so the result must not be trusted. only
+    // possible with xchacha20-poly1305, aes-256-gcm-siv derives the keystream from the tag, so
+    // content that fails authentication can't be told apart from garbage
+    pub fn decrypt_unauthenticated(&self, nonce: &XNonce, encrypted: &[u8]) -> Result<Vec<u8>> {
+        if let Cipher::Aes256GcmSiv(_) = self.cipher {
+            return Err(ErrorKind::MacError.without_source_error());
+        }
+        let content_len = encrypted.len().saturating_sub(POLY1305_TAG_SIZE);
+        let mut content = encrypted[..content_len].to_vec();
+        let mut cipher = XChaCha20::new(Key::from_slice(self.key.expose()), nonce);
+        cipher.seek(CHACHA20_BLOCK_SIZE);
+        cipher.apply_keystream(&mut content);
+        Ok(content)
     }
 }
 
@@ -64,192 +122,895 @@ fn make_mutable_if_immutable(file: &mut File) -> Result<()> {
     }
 }
 
+// the file is replaced by renaming over it, so a symlink must be resolved to replace its target
+// rather than the link itself
+fn canonical_path(path: &Path) -> Result<PathBuf> {
+    io_to_locker_error(path.canonicalize(), ErrorKind::OpenFile)
+}
+
+fn read_whole_file(file: &mut File) -> Result<Vec<u8>> {
+    // get the file length and create a buffer with the retrieved length
+    let file_len = file
+        .metadata()
+        .map(|meta| meta.len() as usize + 1)
+        .unwrap_or(0);
+    let mut content = Vec::with_capacity(file_len);
+
+    // read the whole file into the buffer
+    io_to_locker_error(file.read_to_end(&mut content), ErrorKind::ReadFile)?;
+    Ok(content)
+}
+
+// the key found by the last successful call to test_key
+enum UnlockedKey {
+    // the data key, and the index of the key slot that wraps it, None if the data key was given
+    // directly
+    KeySlot {
+        index: Option<usize>,
+        data_key: Secret<[u8; DERIVED_KEY_SIZE]>,
+    },
+    Derived(Secret<[u8; DERIVED_KEY_SIZE]>),
+    Legacy(LegacyKey),
+    // the hidden vault is decrypted when its password is tested, so its content is kept
+    HiddenVault(Box<UnlockedHiddenVault>),
+}
+
+struct UnlockedHiddenVault {
+    hidden_vault: HiddenVault,
+    encryptor: Encryptor,
+    content: Vec<u8>,
+}
+
+// the index of the first key slot of a password that the password unlocks, and the data key that
+// it wraps. the password is combined with the key file for the key slots that require one
+fn unlock_password_key_slot(
+    key_slots: &[KeySlot],
+    password: &[u8],
+    key_file: Option<&KeyFile>,
+) -> Option<(usize, Secret<[u8; DERIVED_KEY_SIZE]>)> {
+    key_slots.iter().enumerate().find_map(|(index, key_slot)| {
+        // the key slots of recipients are unlocked with identities instead
+        let derivation = key_slot.derivation()?;
+        if !derivation.kdf_params.is_sane() {
+            return None;
+        }
+        let derived_key = derivation.derive_key(password, key_file).ok()?;
+        key_slot
+            .unwrap_key(derived_key.expose())
+            .map(|data_key| (index, data_key))
+    })
+}
+
+enum Headers {
+    KeySlots(EncryptionHeaders),
+    // files written before key slots were added
+    DerivedKey(DerivedKeyHeaders),
+    // files without the magic
+    Legacy(LegacyHeaders),
+}
+
+// decrypts the content, or if authentication isn't required and it fails authentication, decrypts
+// it without verifying it. returns whether the content was authenticated
+fn decrypt_content(
+    encryptor: &Encryptor,
+    nonce: &XNonce,
+    associated_data: &[u8],
+    encrypted: &[u8],
+    require_authentication: bool,
+) -> Result<(Vec<u8>, bool)> {
+    match encryptor.decrypt(nonce, associated_data, encrypted) {
+        Ok(content) => Ok((content, true)),
+        Err(_) if !require_authentication => {
+            Ok((encryptor.decrypt_unauthenticated(nonce, encrypted)?, false))
+        }
+        Err(e) => Err(e),
+    }
+}
+
+// splits what follows the headers of a file with key slots into the encrypted content and the
+// hidden region, which files written before it was added don't have
+fn split_hidden_region<'a>(
+    content: &'a [u8],
+    headers: &EncryptionHeaders,
+) -> (&'a [u8], Option<&'a [u8]>) {
+    let rest = &content[headers.size()..];
+    if headers.version < HIDDEN_REGION_FORMAT_VERSION {
+        return (rest, None);
+    }
+    let (encrypted, hidden_region) = rest.split_at(rest.len() - HIDDEN_REGION_SIZE);
+    (encrypted, Some(hidden_region))
+}
+
 pub struct LockedEncryptedFile {
     file: File,
-    reader: VecReader,
-    headers: EncryptionHeaders,
-    hasher: Sha3_512,
+    path: PathBuf,
+    content: Vec<u8>,
+    headers: Headers,
+    // only used for the key slots that require a key file
+    key_file: Option<KeyFile>,
+    unlocked_key: Option<UnlockedKey>,
 }
 impl LockedEncryptedFile {
+    // the file stays locked until it's dropped, or until the unlocked file is dropped
     pub fn open<P: AsRef<std::path::Path>>(
         path: P,
         open_options: &OpenOptions,
+        lock_mode: LockMode,
     ) -> Result<LockedEncryptedFile> {
-        let mut file = io_to_locker_error(open_options.open(path), ErrorKind::OpenFile)?;
-
-        // get the file length and create a buffer with the retrieved length
-        let file_len = file
-            .metadata()
-            .map(|meta| meta.len() as usize + 1)
-            .unwrap_or(0);
-        let mut content = Vec::with_capacity(file_len);
-
-        // read the whole file into the buffer
-        io_to_locker_error(file.read_to_end(&mut content), ErrorKind::ReadFile)?;
+        let mut file = open_locked(path.as_ref(), open_options, lock_mode)?;
+        let path = canonical_path(path.as_ref())?;
+        let content = read_whole_file(&mut file)?;
 
-        // create a reader over the content vector
-        let mut reader = VecReader::new(content);
-        let headers = EncryptionHeaders::read(&mut reader)?;
+        let headers = match read_format_version(&content) {
+            Some(version @ KEY_SLOTS_FORMAT_VERSION..=FORMAT_VERSION) => {
+                let mut reader = VecReader::new(content[FORMAT_HEADER_SIZE..].to_vec());
+                let headers = EncryptionHeaders::read(&mut reader, version)?;
+                if version >= HIDDEN_REGION_FORMAT_VERSION
+                    && content.len() < headers.size() + HIDDEN_REGION_SIZE
+                {
+                    return Err(ErrorKind::FileNotEncryptedProperly.without_source_error());
+                }
+                Headers::KeySlots(headers)
+            }
+            Some(version @ 1..=FORMAT_VERSION) => {
+                let mut reader = VecReader::new(content[FORMAT_HEADER_SIZE..].to_vec());
+                Headers::DerivedKey(DerivedKeyHeaders::read(&mut reader, version)?)
+            }
+            Some(_) => return Err(ErrorKind::UnsupportedFormatVersion.without_source_error()),
+            None => match LegacyHeaders::read(&content) {
+                Some(legacy_headers) => Headers::Legacy(legacy_headers),
+                None => return Err(ErrorKind::NotEncrypted.without_source_error()),
+            },
+        };
         Ok(LockedEncryptedFile {
             file,
-            reader,
+            path,
+            content,
             headers,
-            hasher: Sha3_512::new(),
+            key_file: None,
+            unlocked_key: None,
         })
     }
     pub fn open_readonly<P: AsRef<std::path::Path>>(path: P) -> Result<LockedEncryptedFile> {
-        LockedEncryptedFile::open(path, OpenOptions::new().read(true))
+        LockedEncryptedFile::open(path, OpenOptions::new().read(true), LockMode::Shared)
     }
     pub fn open_write<P: AsRef<std::path::Path>>(path: P) -> Result<LockedEncryptedFile> {
-        LockedEncryptedFile::open(path, OpenOptions::new().read(true).write(true))
+        LockedEncryptedFile::open(
+            path,
+            OpenOptions::new().read(true).write(true),
+            LockMode::Exclusive,
+        )
     }
 
-    pub fn test_key<B: AsRef<[u8]>>(&mut self, key: B) -> bool {
-        self.hasher.update(&key);
-        self.hasher.update(&self.headers.salt);
-
-        self.hasher.finalize_reset() == self.headers.salted_key_hash
-    }
-    pub fn unlock<B: AsRef<[u8]>>(mut self, key: B) -> Result<EncryptedFile> {
-        let mut encryptor = Encryptor::new(key.as_ref(), &self.headers.nonce);
-        // decrypt the content
-        encryptor.apply(self.reader.rest_mut())?;
-        // validate the hmac
-        self.hasher.update(self.reader.rest());
-        if self.hasher.finalize_reset() != self.headers.hmac {
-            return Err(ErrorKind::MacError.without_source_error());
+    // files without the magic were written before the format was versioned, and can't be told
+    // apart from files that aren't encrypted at all without trying the password
+    pub fn is_legacy(&self) -> bool {
+        matches!(self.headers, Headers::Legacy(_))
+    }
+    pub fn format_version(&self) -> u16 {
+        match &self.headers {
+            Headers::KeySlots(headers) => headers.version,
+            Headers::DerivedKey(headers) => headers.version,
+            Headers::Legacy(_) => UNVERSIONED_FORMAT_VERSION,
         }
-
-        Ok(EncryptedFile {
-            file: self.file,
-            reader: self.reader,
-            encryptor,
-            headers: self.headers,
-            hasher: self.hasher,
-            key: Vec::from(key.as_ref()),
+    }
+    // the structure of the headers is already checked when the file is opened, but the kdf params
+    // are only checked when a key is tried, which reports them as a wrong password
+    pub fn verify_headers(&self) -> Result<()> {
+        let are_kdf_params_sane = match &self.headers {
+            Headers::KeySlots(headers) => headers
+                .key_slots
+                .iter()
+                .filter_map(KeySlot::derivation)
+                .all(|derivation| derivation.kdf_params.is_sane()),
+            Headers::DerivedKey(headers) => headers.kdf_params.is_sane(),
+            Headers::Legacy(_) => true,
+        };
+        if !are_kdf_params_sane {
+            return Err(ErrorKind::FileNotEncryptedProperly.without_source_error());
+        }
+        Ok(())
+    }
+    pub fn verify_private(&self) -> Result<()> {
+        verify_private_file(&self.file)
+    }
+    // whether the file can't be unlocked without a key file
+    pub fn requires_key_file(&self) -> bool {
+        match &self.headers {
+            Headers::KeySlots(headers) => headers.requires_key_file(),
+            Headers::DerivedKey(headers) => headers.derivation().requires_key_file(),
+            Headers::Legacy(_) => false,
+        }
+    }
+    // whether the file can be unlocked with a password, files whose key slots were all revoked
+    // except for those of recipients can only be unlocked with identities
+    pub fn has_password_key_slots(&self) -> bool {
+        match &self.headers {
+            Headers::KeySlots(headers) => headers.has_password_key_slots(),
+            _ => true,
+        }
+    }
+    // whether the file has a key slot of the recipient with the public key
+    pub fn is_encrypted_to(&self, public_key: &RecipientKey) -> bool {
+        self.key_slots().is_some_and(|key_slots| {
+            key_slots
+                .iter()
+                .any(|key_slot| key_slot.recipient() == Some(public_key))
         })
     }
+    // whether any of the ways to unlock the file requires a key file
+    pub fn uses_key_file(&self) -> bool {
+        match &self.headers {
+            Headers::KeySlots(headers) => headers.uses_key_file(),
+            _ => self.requires_key_file(),
+        }
+    }
+    // the key slots are stored in plaintext, so they can be listed without unlocking the file.
+    // None if the file was written before key slots were added
+    pub fn key_slots(&self) -> Option<&[KeySlot]> {
+        match &self.headers {
+            Headers::KeySlots(headers) => Some(&headers.key_slots),
+            _ => None,
+        }
+    }
+    pub fn has_key_file(&self) -> bool {
+        self.key_file.is_some()
+    }
+    // the key file is combined with the keys derived from the passwords that are tried, for the
+    // key slots that require one
+    pub fn set_key_file(&mut self, key_file: KeyFile) {
+        self.key_file = Some(key_file);
+    }
+    fn find_key(&self, password: &[u8]) -> Option<UnlockedKey> {
+        match &self.headers {
+            Headers::KeySlots(headers) => {
+                unlock_password_key_slot(&headers.key_slots, password, self.key_file.as_ref())
+                    .map(|(index, data_key)| UnlockedKey::KeySlot {
+                        index: Some(index),
+                        data_key,
+                    })
+                    .or_else(|| {
+                        // a password that unlocks none of the key slots might be the password of
+                        // a hidden vault, which is tried even if the region is random bytes
+                        let (_, hidden_region) = split_hidden_region(&self.content, headers);
+                        let (hidden_vault, encryptor, content) =
+                            HiddenVault::open(hidden_region?, password)?;
+                        Some(UnlockedKey::HiddenVault(Box::new(UnlockedHiddenVault {
+                            hidden_vault,
+                            encryptor,
+                            content,
+                        })))
+                    })
+            }
+            Headers::DerivedKey(headers) => {
+                if !headers.kdf_params.is_sane() {
+                    return None;
+                }
+                let derived_key = headers
+                    .derivation()
+                    .derive_key(password, self.key_file.as_ref())
+                    .ok()?;
+                if headers.test_derived_key(derived_key.expose()) {
+                    Some(UnlockedKey::Derived(derived_key))
+                } else {
+                    None
+                }
+            }
+            Headers::Legacy(legacy_headers) => {
+                legacy_headers.find_key(password).map(UnlockedKey::Legacy)
+            }
+        }
+    }
+    // unlocks the file with its data key instead of the password of any of its key slots, such as
+    // a data key that was recovered from shares of it
+    pub fn unlock_with_data_key(
+        mut self,
+        data_key: Secret<[u8; DERIVED_KEY_SIZE]>,
+    ) -> Result<EncryptedFile> {
+        if self.key_slots().is_none() {
+            return Err(ErrorKind::NoKeySlots.without_source_error());
+        }
+        self.unlocked_key = Some(UnlockedKey::KeySlot {
+            index: None,
+            data_key,
+        });
+        match self.unlock_content([], true) {
+            Ok((file, _)) => Ok(file),
+            // the content can't be told apart from content that was tampered with
+            Err(e) if matches!(e.kind(), ErrorKind::MacError) => {
+                Err(ErrorKind::WrongDataKey.without_source_error())
+            }
+            Err(e) => Err(e),
+        }
+    }
+    // unlocks the file with the key slot of the recipient with the identity
+    pub fn unlock_with_identity(mut self, identity: &Identity) -> Result<EncryptedFile> {
+        let unlocked_key = match &self.headers {
+            Headers::KeySlots(headers) => {
+                headers
+                    .key_slots
+                    .iter()
+                    .enumerate()
+                    .find_map(|(index, key_slot)| {
+                        key_slot
+                            .unwrap_key_with_identity(identity)
+                            .map(|data_key| UnlockedKey::KeySlot {
+                                index: Some(index),
+                                data_key,
+                            })
+                    })
+            }
+            _ => None,
+        };
+        if unlocked_key.is_none() {
+            return Err(ErrorKind::NotARecipient.without_source_error());
+        }
+        self.unlocked_key = unlocked_key;
+        self.unlock_content([], true).map(|(file, _)| file)
+    }
+    pub fn test_key<B: AsRef<[u8]>>(&mut self, key: B) -> bool {
+        self.unlocked_key = self.find_key(key.as_ref());
+        self.unlocked_key.is_some()
+    }
+    pub fn unlock<B: AsRef<[u8]>>(self, key: B) -> Result<EncryptedFile> {
+        self.unlock_content(key, true).map(|(file, _)| file)
+    }
+    // like unlock, but content that fails authentication is decrypted anyway, so that whatever is
+    // intact in a corrupted file can be salvaged. returns whether the content was authenticated,
+    // if it wasn't it must not be trusted
+    pub fn unlock_unauthenticated<B: AsRef<[u8]>>(self, key: B) -> Result<(EncryptedFile, bool)> {
+        self.unlock_content(key, false)
+    }
+    fn unlock_content<B: AsRef<[u8]>>(
+        mut self,
+        key: B,
+        require_authentication: bool,
+    ) -> Result<(EncryptedFile, bool)> {
+        if self.unlocked_key.is_none() && !self.test_key(key.as_ref()) {
+            return Err(ErrorKind::WrongPassword.without_source_error());
+        }
+        let unlocked_key = match self.unlocked_key.take() {
+            Some(unlocked_key) => unlocked_key,
+            None => return Err(ErrorKind::WrongPassword.without_source_error()),
+        };
+        match (unlocked_key, self.headers) {
+            (UnlockedKey::KeySlot { index, data_key }, Headers::KeySlots(headers)) => {
+                let encryptor = Encryptor::with_cipher_suite(&data_key, headers.cipher_suite);
+                let (encrypted, hidden_region) = split_hidden_region(&self.content, &headers);
+                let (content, authenticated) = decrypt_content(
+                    &encryptor,
+                    &headers.nonce,
+                    &headers.associated_data(),
+                    encrypted,
+                    require_authentication,
+                )?;
+                let encrypted = encrypted.to_vec();
+                let hidden_region = hidden_region.map_or_else(random_hidden_region, <[u8]>::to_vec);
+                let requires_key_file = match index {
+                    Some(index) => headers.key_slots[index].requires_key_file(),
+                    None => false,
+                };
+                let file = EncryptedFile {
+                    file: self.file,
+                    path: self.path,
+                    backups: None,
+                    reader: VecReader::new(content),
+                    headers,
+                    unlocked_key_slot: index,
+                    // content that wasn't authenticated is re-encrypted when it's written
+                    encrypted: if authenticated { Some(encrypted) } else { None },
+                    hidden_region,
+                    hidden_vault: None,
+                    key_file: if requires_key_file { self.key_file } else { None },
+                    encryptor,
+                };
+                Ok((file, authenticated))
+            }
+            (UnlockedKey::HiddenVault(unlocked_hidden_vault), Headers::KeySlots(headers)) => {
+                let UnlockedHiddenVault {
+                    hidden_vault,
+                    encryptor,
+                    content,
+                } = *unlocked_hidden_vault;
+                // the content of the file itself can't be decrypted, so it's kept as it is
+                let (encrypted, hidden_region) = split_hidden_region(&self.content, &headers);
+                let (encrypted, hidden_region) = match hidden_region {
+                    Some(hidden_region) => (encrypted.to_vec(), hidden_region.to_vec()),
+                    None => return Err(ErrorKind::FileNotEncryptedProperly.without_source_error()),
+                };
+                let file = EncryptedFile {
+                    file: self.file,
+                    path: self.path,
+                    backups: None,
+                    reader: VecReader::new(content),
+                    headers,
+                    unlocked_key_slot: None,
+                    encrypted: Some(encrypted),
+                    hidden_region,
+                    hidden_vault: Some(hidden_vault),
+                    key_file: None,
+                    encryptor,
+                };
+                Ok((file, true))
+            }
+            (UnlockedKey::Derived(derived_key), Headers::DerivedKey(derived_key_headers)) => {
+                let (content, authenticated) = decrypt_content(
+                    &Encryptor::new(&derived_key),
+                    &derived_key_headers.nonce,
+                    &derived_key_headers.to_bytes(),
+                    &self.content[derived_key_headers.size()..],
+                    require_authentication,
+                )?;
+                // the file is written with a key slot that wraps a new data key with the same
+                // derived key, so the key doesn't have to be derived again
+                let derivation = derived_key_headers.derivation();
+                let data_key = new_data_key();
+                let key_slot = KeySlot::new(
+                    DEFAULT_KEY_SLOT_LABEL.to_string(),
+                    derivation,
+                    derived_key.expose(),
+                    data_key.expose(),
+                )?;
+                let mut headers = EncryptionHeaders::with_key_slot(key_slot, CipherSuite::DEFAULT);
+                headers.version = derived_key_headers.version;
+                let file = EncryptedFile {
+                    file: self.file,
+                    path: self.path,
+                    backups: None,
+                    reader: VecReader::new(content),
+                    headers,
+                    unlocked_key_slot: Some(0),
+                    encrypted: None,
+                    hidden_region: random_hidden_region(),
+                    hidden_vault: None,
+                    key_file: if derivation.requires_key_file() {
+                        self.key_file
+                    } else {
+                        None
+                    },
+                    encryptor: Encryptor::new(data_key),
+                };
+                Ok((file, authenticated))
+            }
+            (UnlockedKey::Legacy(legacy_key), Headers::Legacy(legacy_headers)) => {
+                let (content, authenticated) =
+                    legacy_headers.decrypt(&self.content, &legacy_key, require_authentication)?;
+                // derive a key for the legacy file, which is written to it in the current format
+                // the next time it's flushed
+                let (mut headers, data_key) =
+                    EncryptionHeaders::new(key, KdfParams::DEFAULT, CipherSuite::DEFAULT)?;
+                headers.version = UNVERSIONED_FORMAT_VERSION;
+                let file = EncryptedFile {
+                    file: self.file,
+                    path: self.path,
+                    backups: None,
+                    reader: VecReader::new(content),
+                    headers,
+                    unlocked_key_slot: Some(0),
+                    encrypted: None,
+                    hidden_region: random_hidden_region(),
+                    hidden_vault: None,
+                    key_file: None,
+                    encryptor: Encryptor::new(data_key),
+                };
+                Ok((file, authenticated))
+            }
+            _ => Err(ErrorKind::FileNotEncryptedProperly.without_source_error()),
+        }
+    }
 }
 pub struct EncryptedFile {
     file: File,
+    // the path that the file is replaced at when it's written
+    path: PathBuf,
+    // if set, the file is backed up before each time it's written
+    backups: Option<Backups>,
+    // the decrypted content
     reader: VecReader,
     headers: EncryptionHeaders,
-    hasher: Sha3_512,
-    key: Vec<u8>,
+    // the index of the key slot that the file was unlocked with, None if it was revoked
+    unlocked_key_slot: Option<usize>,
+    // the encrypted content as it is in the file, so that the key slots can be changed without
+    // re-encrypting it. None if it must be re-encrypted before it's written
+    encrypted: Option<Vec<u8>>,
+    // written after the encrypted content as it is, unless the file was unlocked with the password
+    // of the hidden vault in it
+    hidden_region: Vec<u8>,
+    // set if the file was unlocked with the password of its hidden vault, in which case the content
+    // is the content of the hidden vault, and the encrypted content of the file is left as it is
+    hidden_vault: Option<HiddenVault>,
+    // if set, the key slot is combined with it the next time the key is changed
+    key_file: Option<KeyFile>,
+    // encrypts the content with the data key
     encryptor: Encryptor,
 }
 impl EncryptedFile {
     pub fn create<P: AsRef<std::path::Path>, B: AsRef<[u8]>>(
         path: P,
         key: B,
+        kdf_params: KdfParams,
+        cipher_suite: CipherSuite,
     ) -> Result<EncryptedFile> {
-        let mut file = io_to_locker_error(
+        // the file isn't truncated since it's only replaced once it's locked
+        let file = open_locked(
+            path.as_ref(),
             OpenOptions::new()
                 .read(true)
                 .write(true)
                 .create(true)
-                .open(path),
-            ErrorKind::OpenFile,
+                .truncate(false)
+                .mode(PRIVATE_FILE_MODE),
+            LockMode::Exclusive,
         )?;
 
-        let mut hasher = Sha3_512::new();
-        let content = Vec::new();
-        let headers = EncryptionHeaders::new(&mut hasher, &content, key.as_ref());
-        let mut headers_buf = [0u8; ENCRYPTION_HEADERS_SIZE];
-        headers.write_to(&mut headers_buf);
-        io_to_locker_error(file.write_all(&headers_buf), ErrorKind::WriteFile)?;
-
-        // encrypt the content buffer
-        Ok(EncryptedFile {
-            encryptor: Encryptor::new(key.as_ref(), &headers.nonce),
-            key: Vec::from(key.as_ref()),
-            reader: VecReader::new(content),
+        let (headers, data_key) = EncryptionHeaders::new(key, kdf_params, cipher_suite)?;
+        let mut encrypted_file = EncryptedFile {
+            encryptor: Encryptor::with_cipher_suite(data_key, cipher_suite),
+            reader: VecReader::new(Vec::new()),
             file,
+            path: canonical_path(path.as_ref())?,
+            backups: None,
             headers,
-            hasher,
-        })
+            unlocked_key_slot: Some(0),
+            encrypted: None,
+            hidden_region: random_hidden_region(),
+            hidden_vault: None,
+            key_file: None,
+        };
+        encrypted_file.write_content(Vec::new())?;
+        Ok(encrypted_file)
     }
     pub fn encrypt_file<P: AsRef<std::path::Path>, B: AsRef<[u8]>>(
         path: P,
         key: B,
+        kdf_params: KdfParams,
+        cipher_suite: CipherSuite,
     ) -> Result<EncryptedFile> {
-        let mut file = io_to_locker_error(
-            OpenOptions::new().read(true).write(true).open(path),
-            ErrorKind::OpenFile,
+        let mut file = open_locked(
+            path.as_ref(),
+            OpenOptions::new().read(true).write(true),
+            LockMode::Exclusive,
         )?;
+        let content = read_whole_file(&mut file)?;
+        if read_format_version(&content).is_some() {
+            return Err(ErrorKind::AlreadyEncrypted.without_source_error());
+        }
         make_mutable_if_immutable(&mut file)?;
 
-        // get the file length and create a buffer with the retrieved length
-        let file_len = file
-            .metadata()
-            .map(|meta| meta.len() as usize + 1)
-            .unwrap_or(0);
-        let mut content = Vec::with_capacity(file_len);
-
-        io_to_locker_error(file.read_to_end(&mut content), ErrorKind::ReadFile)?;
-
-        // seek back to the start of the file for later write calls
-        io_to_locker_error(file.seek(SeekFrom::Start(0)), ErrorKind::SeekFile)?;
-
-        let mut hasher = Sha3_512::new();
-        let headers = EncryptionHeaders::new(&mut hasher, &content, key.as_ref());
-        let mut headers_buf = [0u8; ENCRYPTION_HEADERS_SIZE];
-        headers.write_to(&mut headers_buf);
-        io_to_locker_error(file.write_all(&headers_buf), ErrorKind::WriteFile)?;
-
-        // encrypt the content buffer
-        let mut encryptor = Encryptor::new(key.as_ref(), &headers.nonce);
-        encryptor.apply(&mut content)?;
-
-        // write the encrypted content
-        match file.write_all(&content) {
-            Ok(()) => Ok(EncryptedFile {
-                key: Vec::from(key.as_ref()),
-                reader: VecReader::new(content),
-                file,
-                headers,
-                hasher,
-                encryptor,
-            }),
-            Err(err) => {
-                // if failed to write the content, rewrite the original content
-                // that was overwritten when writing the headers to the file
-                match file.write_all(&content[..ENCRYPTION_HEADERS_SIZE]) {
-                    Ok(()) => Err(ErrorKind::WriteFile.with_source_error(err)),
-                    Err(backup_err) => Err(ErrorKind::RevertToBackup.with_source_error(backup_err)),
-                }
-            }
-        }
+        let (headers, data_key) = EncryptionHeaders::new(key, kdf_params, cipher_suite)?;
+        let mut encrypted_file = EncryptedFile {
+            encryptor: Encryptor::with_cipher_suite(data_key, cipher_suite),
+            reader: VecReader::new(Vec::new()),
+            file,
+            path: canonical_path(path.as_ref())?,
+            backups: None,
+            headers,
+            unlocked_key_slot: Some(0),
+            encrypted: None,
+            hidden_region: random_hidden_region(),
+            hidden_vault: None,
+            key_file: None,
+        };
+        encrypted_file.write_content(content)?;
+        Ok(encrypted_file)
     }
     pub fn decrypt(mut self) -> Result<()> {
-        // right now the file cursor is at the end of the file because we called read_to_end,
-        // so seek to the start of the file to write the decrypted content
-        io_to_locker_error(self.file.seek(SeekFrom::Start(0)), ErrorKind::SeekFile)?;
-        // write all the decrypted content to the file
-        io_to_locker_error(
-            self.file.write_all(self.reader.rest()),
-            ErrorKind::WriteFile,
-        )?;
-
-        // truncate the rest of the file
-        io_to_locker_error(
-            self.file.set_len(self.reader.rest().len() as u64),
-            ErrorKind::TruncateFile,
+        replace_file(&self.path, &mut self.file, &[self.reader.buffer()])?;
+        Ok(())
+    }
+    pub fn key_slots(&self) -> &[KeySlot] {
+        &self.headers.key_slots
+    }
+    pub fn cipher_suite(&self) -> CipherSuite {
+        self.headers.cipher_suite
+    }
+    // None if the file wasn't unlocked with a key slot, or if its key slot was revoked
+    pub fn unlocked_key_slot(&self) -> Option<usize> {
+        self.unlocked_key_slot
+    }
+    // whether the password unlocks any of the key slots of passwords, which are tried before the
+    // hidden vault when the file is unlocked
+    pub fn password_unlocks_key_slot<B: AsRef<[u8]>>(&self, password: B) -> bool {
+        unlock_password_key_slot(
+            &self.headers.key_slots,
+            password.as_ref(),
+            self.key_file.as_ref(),
         )
+        .is_some()
+    }
+    // anyone with the threshold of the shares can unlock the file, regardless of its key slots,
+    // until the content is encrypted with another data key
+    pub fn split_data_key(&self, shares: u8, threshold: u8) -> Result<Vec<KeyShare>> {
+        self.verify_not_hidden_vault()?;
+        split_key(self.encryptor.key.expose(), shares, threshold)
+    }
+    // the kdf params of the key slot that the file was unlocked with, or of the first key slot of
+    // a password if it wasn't unlocked with one
+    pub fn kdf_params(&self) -> KdfParams {
+        let unlocked_derivation = self
+            .unlocked_key_slot
+            .and_then(|index| self.headers.key_slots[index].derivation());
+        unlocked_derivation
+            .or_else(|| self.headers.key_slots.iter().find_map(KeySlot::derivation))
+            .map_or(KdfParams::DEFAULT, |derivation| derivation.kdf_params)
+    }
+    pub fn set_backups(&mut self, backups: Backups) {
+        self.backups = Some(backups);
     }
-    fn seek_file(&mut self, pos: u64) -> Result<u64> {
-        io_to_locker_error(self.file.seek(SeekFrom::Start(pos)), ErrorKind::SeekFile)
+    // sets the key file that the key slot is combined with, or that it doesn't require one, which
+    // only takes effect once the key is changed
+    pub fn set_key_file(&mut self, key_file: Option<KeyFile>) {
+        self.key_file = key_file;
     }
-    fn write_hmac_and_nonce(&mut self) -> Result<()> {
-        io_to_locker_error(
-            self.file.write_all(&self.headers.hmac),
-            ErrorKind::WriteFile,
+    // the format version of the decrypted content, which is the current version once the file has
+    // been written
+    pub fn format_version(&self) -> u16 {
+        match &self.hidden_vault {
+            Some(hidden_vault) => hidden_vault.version,
+            None => self.headers.version,
+        }
+    }
+    // the key slots belong to the file itself, so they can't be changed or used by a hidden vault.
+    // the error doesn't mention the hidden vault, since that would prove that there is one to
+    // whoever made the user enter the duress password
+    fn verify_not_hidden_vault(&self) -> Result<()> {
+        match self.hidden_vault {
+            Some(_) => Err(ErrorKind::HiddenVaultUnlocked.without_source_error()),
+            None => Ok(()),
+        }
+    }
+    // replaces whatever is in the hidden region with a new empty hidden vault, which the file is
+    // switched to. the hidden vault is only written once the caller writes its content
+    pub fn create_hidden_vault<B: AsRef<[u8]>>(&mut self, password: B) -> Result<()> {
+        self.verify_not_hidden_vault()?;
+        // the content of the file is written as it is from now on, so it must already be in the
+        // current format
+        if self.encrypted.is_none() || self.headers.version != FORMAT_VERSION {
+            self.rewrite()?;
+        }
+        let (hidden_vault, encryptor) = HiddenVault::new(password.as_ref())?;
+        self.hidden_vault = Some(hidden_vault);
+        self.encryptor = encryptor;
+        self.reader = VecReader::new(Vec::new());
+        self.unlocked_key_slot = None;
+        self.key_file = None;
+        Ok(())
+    }
+    // fills the hidden region with new random bytes, which destroys any hidden vault in it
+    pub fn remove_hidden_vault(&mut self) -> Result<()> {
+        self.verify_not_hidden_vault()?;
+        self.hidden_region = random_hidden_region();
+        self.write_key_slots()
+    }
+    // encrypts the given content with a new nonce and replaces the content of the file with it.
+    // the content must be in the current format
+    fn write_content(&mut self, content: Vec<u8>) -> Result<()> {
+        // the content is wiped if it fails to be written
+        let mut content = Secret::new(content);
+        if let Some(hidden_vault) = &mut self.hidden_vault {
+            self.hidden_region = hidden_vault.seal(&self.encryptor, &content)?;
+            let encrypted = match self.encrypted.take() {
+                Some(encrypted) => encrypted,
+                None => return Err(ErrorKind::FileNotEncryptedProperly.without_source_error()),
+            };
+            // not backed up, since consecutive backups that only differ in the hidden region would
+            // reveal that a hidden vault is being written to
+            self.replace_encrypted(encrypted)?;
+            self.reader = VecReader::new(content.take());
+            return Ok(());
+        }
+        self.headers.version = FORMAT_VERSION;
+        // regenerate a new random nonce - never reuse the same nonce with the same key!!
+        thread_rng().fill_bytes(&mut self.headers.nonce);
+        let encrypted = self.encryptor.encrypt(
+            &self.headers.nonce,
+            &self.headers.associated_data(),
+            &content,
+        )?;
+        self.write_encrypted(encrypted)?;
+        self.reader = VecReader::new(content.take());
+        Ok(())
+    }
+    fn write_encrypted(&mut self, encrypted: Vec<u8>) -> Result<()> {
+        if let Some(backups) = &self.backups {
+            backups.save(&self.path)?;
+        }
+        self.replace_encrypted(encrypted)
+    }
+    fn replace_encrypted(&mut self, encrypted: Vec<u8>) -> Result<()> {
+        let headers = self.headers.to_bytes();
+        self.file = replace_file(
+            &self.path,
+            &mut self.file,
+            &[&headers, &encrypted, &self.hidden_region],
         )?;
-        io_to_locker_error(
-            self.file.write_all(&self.headers.nonce),
-            ErrorKind::WriteFile,
+        self.encrypted = Some(encrypted);
+        Ok(())
+    }
+    // writes the key slots along with the content as it's already encrypted, unless it must be
+    // re-encrypted, in which case the file is rewritten in the current format, which fails if its
+    // content hasn't been converted to the current layout
+    fn write_key_slots(&mut self) -> Result<()> {
+        match self.encrypted.take() {
+            Some(encrypted) if self.headers.version == FORMAT_VERSION => {
+                self.write_encrypted(encrypted)
+            }
+            _ => self.rewrite(),
+        }
+    }
+    // rewrites the content of the file in the current format. only the headers are converted, so
+    // the content must already be in the current layout. it isn't if it's the content of a
+    // passwords file whose entries haven't been converted yet, which would be misread once it's
+    // written with the current format version
+    pub fn rewrite(&mut self) -> Result<()> {
+        if self.format_version() < CONTENT_LAYOUT_FORMAT_VERSION {
+            return Err(ErrorKind::ContentNotConverted.without_source_error());
+        }
+        self.rewrite_unversioned_content()
+    }
+    // rewrites the content of the file in the current format as it is, for content that doesn't
+    // depend on the format version, such as the content of a locked file
+    pub fn rewrite_unversioned_content(&mut self) -> Result<()> {
+        let content = self.reader.buffer().to_vec();
+        self.write_content(content)
+    }
+    fn new_key_slot(
+        &self,
+        label: String,
+        password: &[u8],
+        kdf_params: KdfParams,
+        key_file: Option<&KeyFile>,
+    ) -> Result<KeySlot> {
+        let derivation = KeyDerivation::new(kdf_params, key_file.is_some());
+        let derived_key = derivation.derive_key(password, key_file)?;
+        KeySlot::new(
+            label,
+            derivation,
+            derived_key.expose(),
+            self.encryptor.key.expose(),
         )
     }
+    // the index of the key slot of the password that the file was unlocked with
+    fn unlocked_password_key_slot(&self) -> Option<usize> {
+        self.unlocked_key_slot
+            .filter(|index| self.headers.key_slots[*index].derivation().is_some())
+    }
+    // replaces the key slot of the password that the file was unlocked with by one for the new
+    // password, combined with the key file if one is set, or adds one if it wasn't unlocked with a
+    // password. the other key slots still unlock the file
+    pub fn change_key<B: AsRef<[u8]>>(&mut self, new_key: B, kdf_params: KdfParams) -> Result<()> {
+        self.verify_not_hidden_vault()?;
+        let unlocked_password_key_slot = self.unlocked_password_key_slot();
+        let label = match unlocked_password_key_slot {
+            Some(index) => self.headers.key_slots[index].label.clone(),
+            None if self.headers.key_slots.len() >= MAX_KEY_SLOTS => {
+                return Err(ErrorKind::TooManyKeySlots.without_source_error())
+            }
+            None => DEFAULT_KEY_SLOT_LABEL.to_string(),
+        };
+        let key_slot =
+            self.new_key_slot(label, new_key.as_ref(), kdf_params, self.key_file.as_ref())?;
+        match unlocked_password_key_slot {
+            Some(index) => self.headers.key_slots[index] = key_slot,
+            None => {
+                self.headers.key_slots.push(key_slot);
+                self.unlocked_key_slot = Some(self.headers.key_slots.len() - 1);
+            }
+        }
+        self.write_key_slots()
+    }
+    // the index of the key slot of the recipient with the public key
+    pub fn recipient_key_slot(&self, public_key: &RecipientKey) -> Option<usize> {
+        self.headers
+            .key_slots
+            .iter()
+            .position(|key_slot| key_slot.recipient() == Some(public_key))
+    }
+    // adds a key slot that wraps the data key to the recipient with the public key. returns the
+    // index of the new slot
+    pub fn add_recipient(&mut self, label: String, public_key: &RecipientKey) -> Result<usize> {
+        self.verify_not_hidden_vault()?;
+        if self.recipient_key_slot(public_key).is_some() {
+            return Err(ErrorKind::RecipientAlreadyAdded.without_source_error());
+        }
+        if self.headers.key_slots.len() >= MAX_KEY_SLOTS {
+            return Err(ErrorKind::TooManyKeySlots.without_source_error());
+        }
+        let key_slot = KeySlot::to_recipient(label, public_key, self.encryptor.key.expose())?;
+        self.headers.key_slots.push(key_slot);
+        self.write_key_slots()?;
+        Ok(self.headers.key_slots.len() - 1)
+    }
+    // the key slots of the passwords that the file wasn't unlocked with, which can't be wrapped
+    // with a new data key without their passwords
+    pub fn other_password_key_slots(&self) -> Vec<usize> {
+        let unlocked_password_key_slot = self.unlocked_password_key_slot();
+        (0..self.headers.key_slots.len())
+            .filter(|index| {
+                self.headers.key_slots[*index].derivation().is_some()
+                    && Some(*index) != unlocked_password_key_slot
+            })
+            .collect()
+    }
+    // removes the key slot of the recipient with the given index, and replaces the data key, so
+    // that a recipient that kept the data key can't decrypt what is written from now on. the new
+    // data key is wrapped to the other recipients and with the password that the file was unlocked
+    // with, which must be given, while the other key slots of passwords are revoked. the content
+    // isn't written, so the caller must write it with the new data key, such as with rewrite
+    pub fn remove_recipient<B: AsRef<[u8]>>(&mut self, index: usize, password: B) -> Result<()> {
+        self.verify_not_hidden_vault()?;
+        if self.headers.key_slots.get(index).and_then(KeySlot::recipient).is_none() {
+            return Err(ErrorKind::NoSuchRecipient.without_source_error());
+        }
+        if self.headers.key_slots.len() == 1 {
+            return Err(ErrorKind::LastKeySlot.without_source_error());
+        }
+        let unlocked_password_key_slot = self.unlocked_password_key_slot();
+        let data_key = new_data_key();
+        let mut key_slots = Vec::with_capacity(self.headers.key_slots.len());
+        let mut unlocked_key_slot = None;
+        for (slot_index, key_slot) in self.headers.key_slots.iter().enumerate() {
+            if slot_index == index {
+                continue;
+            }
+            if let Some(public_key) = key_slot.recipient() {
+                let label = key_slot.label.clone();
+                key_slots.push(KeySlot::to_recipient(label, public_key, data_key.expose())?);
+            } else if Some(slot_index) == unlocked_password_key_slot {
+                let derivation =
+                    KeyDerivation::new(self.kdf_params(), key_slot.requires_key_file());
+                let derived_key =
+                    derivation.derive_key(password.as_ref(), self.key_file.as_ref())?;
+                key_slots.push(KeySlot::new(
+                    key_slot.label.clone(),
+                    derivation,
+                    derived_key.expose(),
+                    data_key.expose(),
+                )?);
+                unlocked_key_slot = Some(key_slots.len() - 1);
+            }
+        }
+        self.headers.key_slots = key_slots;
+        self.unlocked_key_slot = unlocked_key_slot;
+        self.encryptor = Encryptor::with_cipher_suite(data_key, self.headers.cipher_suite);
+        self.encrypted = None;
+        Ok(())
+    }
+    // adds a key slot that unlocks the file with the given password, and the key file if given.
+    // returns the index of the new slot
+    pub fn add_key_slot<B: AsRef<[u8]>>(
+        &mut self,
+        label: String,
+        password: B,
+        kdf_params: KdfParams,
+        key_file: Option<&KeyFile>,
+    ) -> Result<usize> {
+        self.verify_not_hidden_vault()?;
+        if self.headers.key_slots.len() >= MAX_KEY_SLOTS {
+            return Err(ErrorKind::TooManyKeySlots.without_source_error());
+        }
+        let key_slot = self.new_key_slot(label, password.as_ref(), kdf_params, key_file)?;
+        self.headers.key_slots.push(key_slot);
+        self.write_key_slots()?;
+        Ok(self.headers.key_slots.len() - 1)
+    }
+    // removes the key slot with the given index, the last key slot can't be removed
+    pub fn revoke_key_slot(&mut self, index: usize) -> Result<()> {
+        self.verify_not_hidden_vault()?;
+        if index >= self.headers.key_slots.len() {
+            return Err(ErrorKind::NoSuchKeySlot.without_source_error());
+        }
+        if self.headers.key_slots.len() == 1 {
+            return Err(ErrorKind::LastKeySlot.without_source_error());
+        }
+        self.headers.key_slots.remove(index);
+        self.unlocked_key_slot = match self.unlocked_key_slot {
+            Some(unlocked) if unlocked == index => None,
+            Some(unlocked) if unlocked > index => Some(unlocked - 1),
+            unlocked => unlocked,
+        };
+        self.write_key_slots()
+    }
     // using self instead of &mut self because modifying the content vector causes a problem with
     // the position of the VecReader, so using self prevents the use of the struct after this
     // corruption
@@ -299,7 +1060,7 @@ impl EncryptedFile {
     //     self.write_hmac_and_nonce()?;
     //     Ok(())
     // }
-    pub fn reader(&mut self) -> EncryptedFileReader {
+    pub fn reader(&mut self) -> EncryptedFileReader<'_> {
         EncryptedFileReader::new(self)
     }
     pub fn writer(&'_ mut self) -> EncryptedFileWriter<'_> {
@@ -0,0 +1,106 @@
+use super::io::{read_bytes, read_exact, read_until};
+use crate::locker::{EncryptedRead, EncryptedWrite, Encryptor, Result, DERIVED_KEY_SIZE};
+use crate::secret::Secret;
+use crate::vec_io::VecReader;
+use chacha20poly1305::XNonce;
+use rand::{thread_rng, RngCore};
+use sha3::{Digest, Sha3_256};
+
+pub const SEALED_ID_SIZE: usize = 16;
+const SUBKEY_CONTEXT: &[u8] = b"pwm sealed subkey";
+
+// a part of the content that is encrypted on its own, with its own nonce and a subkey of the data
+// key that is derived from its random id, so that it can be decrypted without decrypting anything
+// else
+pub struct Sealed {
+    id: [u8; SEALED_ID_SIZE],
+    nonce: XNonce,
+    encrypted: Vec<u8>,
+}
+impl Sealed {
+    pub fn read<R: EncryptedRead>(reader: &mut R) -> Result<Sealed> {
+        let mut sealed = Sealed {
+            id: [0u8; SEALED_ID_SIZE],
+            nonce: XNonce::default(),
+            encrypted: Vec::new(),
+        };
+        reader.read_exact(&mut sealed.id)?;
+        reader.read_exact(&mut sealed.nonce)?;
+        let mut length = [0u8; std::mem::size_of::<u64>()];
+        reader.read_exact(&mut length)?;
+        sealed.encrypted = reader.read_bytes(u64::from_le_bytes(length))?;
+        Ok(sealed)
+    }
+    pub fn write_to<'a, W: EncryptedWrite>(&self, writer: &'a mut W) -> &'a mut W {
+        writer
+            .write_all(&self.id)
+            .write_all(&self.nonce)
+            .write_all(&(self.encrypted.len() as u64).to_le_bytes())
+            .write_all(&self.encrypted)
+    }
+}
+
+impl Encryptor {
+    fn subkey(&self, id: &[u8]) -> Encryptor {
+        let mut hasher = Sha3_256::new();
+        hasher.update(SUBKEY_CONTEXT);
+        hasher.update(self.key.expose());
+        hasher.update(id);
+        let mut subkey = Secret::new([0u8; DERIVED_KEY_SIZE]);
+        subkey.expose_mut().copy_from_slice(&hasher.finalize());
+        Encryptor::with_cipher_suite(subkey, self.cipher_suite())
+    }
+    // a new id is generated each time something is sealed, so no subkey ever encrypts twice
+    pub fn seal(&self, associated_data: &[u8], content: &[u8]) -> Result<Sealed> {
+        let mut id = [0u8; SEALED_ID_SIZE];
+        thread_rng().fill_bytes(&mut id);
+        let mut nonce = XNonce::default();
+        thread_rng().fill_bytes(&mut nonce);
+        let encrypted = self.subkey(&id).encrypt(&nonce, associated_data, content)?;
+        Ok(Sealed {
+            id,
+            nonce,
+            encrypted,
+        })
+    }
+    pub fn unseal(&self, sealed: &Sealed, associated_data: &[u8]) -> Result<VecReader> {
+        let content =
+            self.subkey(&sealed.id)
+                .decrypt(&sealed.nonce, associated_data, &sealed.encrypted)?;
+        Ok(VecReader::new(content))
+    }
+}
+
+// reads the decrypted content of a sealed part of the content
+pub struct SealedReader {
+    reader: VecReader,
+    format_version: u16,
+}
+impl SealedReader {
+    pub fn new(reader: VecReader, format_version: u16) -> SealedReader {
+        SealedReader {
+            reader,
+            format_version,
+        }
+    }
+}
+impl EncryptedRead for SealedReader {
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<()> {
+        read_exact(&mut self.reader, buf)
+    }
+    fn read_bytes(&mut self, amount: u64) -> Result<Vec<u8>> {
+        read_bytes(&mut self.reader, amount)
+    }
+    fn read_until(&mut self, terminator: u8) -> Result<Vec<u8>> {
+        read_until(&mut self.reader, terminator)
+    }
+    fn offset(&self) -> usize {
+        self.reader.position()
+    }
+    fn peek(&self) -> Option<u8> {
+        self.reader.rest().first().copied()
+    }
+    fn format_version(&self) -> u16 {
+        self.format_version
+    }
+}
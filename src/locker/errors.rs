@@ -1,26 +1,75 @@
 #[derive(Debug)]
 pub enum ErrorKind {
     OpenFile,
+    LockFile,
+    FileLocked,
     ReadFile,
     WriteFile,
-    SeekFile,
-    TruncateFile,
     FileGetFlags,
     FileSetFlags,
     FileNotEncryptedProperly,
-    GetFileMetadata,
-    FileTooBig,
+    FileOwnedByAnotherUser,
+    // the permission bits of the file
+    FileAccessibleByOthers { mode: u32 },
+    UnsupportedFormatVersion,
+    UnsupportedCipherSuite,
+    AlreadyEncrypted,
+    NotEncrypted,
+    LegacyFormat,
+    ContentNotConverted,
     MacError,
     WrongPassword,
     EncryptionError,
-    RevertToBackup,
+    KeyDerivationError,
+    ReplaceFile,
+    Backup,
+    NoSuchBackup,
+    FileExists,
+    ReadConfig,
+    ReadKeyFile,
+    CreateKeyFile,
+    KeyFileRequired,
+    KeyFileAlreadyRequired,
+    KeyFileNotRequired,
+    KeySlotLabelTooLong,
+    TooManyKeySlots,
+    NoSuchKeySlot,
+    LastKeySlot,
+    NoKeySlots,
+    WrongDataKey,
+    InvalidKeyShare,
+    InvalidShareThreshold,
+    NotEnoughKeyShares,
+    KeySharesOfDifferentSplits,
+    ReadIdentity,
+    CreateIdentity,
+    InvalidIdentity,
+    NoIdentity,
+    IdentityExists,
+    InvalidPublicKey,
+    NotARecipient,
+    RecipientAlreadyAdded,
+    NoSuchRecipient,
+    InvalidSharedFile,
+    HiddenVaultFull,
+    HiddenVaultUnlocked,
+    DuressPasswordUnlocksKeySlot,
+    HardenProcess,
+    ProcessTraced,
+    InvalidConfig { line: usize },
     PromptPasswordIOError,
+    PromptConfirmationIOError,
     EncodingError,
-    CorruptedFile,
+    // the offset is in the decrypted content
+    CorruptedFile { offset: usize },
     HomeDir,
     EmptyPasswordDict,
     PasswordLengthZero,
     CopyToClipboard,
+    PasswordsDontMatch,
+    Editor,
+    NoMatchingEntry,
+    MultipleMatchingEntries,
 }
 impl ErrorKind {
     pub fn without_source_error(self) -> Error {
@@ -68,39 +117,88 @@ pub fn to_locker_error<T, E>(
 ) -> Result<T> {
     match result {
         Ok(v) => Ok(v),
-        Err(e) => Err(Error {
+        Err(_) => Err(Error {
             kind: error_kind,
             source_error: None,
         }),
     }
 }
-pub fn print_error(error: Error, file_prefix: &str, error_style: &ansi_term::Style) {
-    let source_error_str = match error.source_error {
+pub fn error_message(error: &Error, file_prefix: &str) -> String {
+    let source_error_str = match &error.source_error {
         Some(e) => format!(": {}", e),
         None => String::new(),
     };
-    let err=match error.kind{
+    match error.kind{
         ErrorKind::OpenFile=>format!("Failed to open the {} file{}",file_prefix,source_error_str),
+        ErrorKind::LockFile=>format!("Failed to lock the {} file{}",file_prefix,source_error_str),
+        ErrorKind::FileLocked=>format!("The {} file is being used by another pwm process",file_prefix),
         ErrorKind::ReadFile=>format!("Failed to read the {} file{}",file_prefix,source_error_str),
         ErrorKind::WriteFile=>format!("Failed to write to the {} file{}",file_prefix,source_error_str),
-        ErrorKind::SeekFile=>format!("Failed to seek inside the {} file{}",file_prefix,source_error_str),
-        ErrorKind::TruncateFile=>format!("Failed to truncate the {} file{}",file_prefix,source_error_str),
         ErrorKind::FileGetFlags=>format!("Failed to get the unix file flags of the {} file{}",file_prefix,source_error_str),
         ErrorKind::FileSetFlags=>format!("Failed to set the unix file flags of the {} file{}",file_prefix,source_error_str),
         ErrorKind::FileNotEncryptedProperly=>format!("The {} file is corrupted or not encrypted properly{}",file_prefix,source_error_str),
-        ErrorKind::GetFileMetadata=>format!("Failed to get the file metadata of the {} file{}",file_prefix,source_error_str),
-        ErrorKind::FileTooBig=>format!("The {} file is too big{}",file_prefix,source_error_str),
-        ErrorKind::MacError=>format!("The {} file's MAC is invalid, the file is corrupted or not encrypted properly{}",file_prefix,source_error_str),
-        ErrorKind::WrongPassword=>"An unexpected error has occured (wrong password error)".to_string(),
+        ErrorKind::FileOwnedByAnotherUser=>format!("The {} file is owned by another user",file_prefix),
+        ErrorKind::FileAccessibleByOthers{mode}=>format!("The {} file can be accessed by other users since its mode is {:o}, run `chmod 600` on it",file_prefix,mode),
+        ErrorKind::UnsupportedFormatVersion=>format!("The {} file was written by a newer version of pwm",file_prefix),
+        ErrorKind::UnsupportedCipherSuite=>format!("The {} file is encrypted with a cipher suite that was added by a newer version of pwm",file_prefix),
+        ErrorKind::AlreadyEncrypted=>format!("The {} file is already locked",file_prefix),
+        ErrorKind::NotEncrypted=>format!("The {} file is not locked",file_prefix),
+        ErrorKind::LegacyFormat=>format!("The {} file is not locked, or was locked by an older version of pwm, in which case run `pwm upgrade` on it first",file_prefix),
+        ErrorKind::ContentNotConverted=>format!("The {} file was written by an older version of pwm, run `pwm upgrade` on it first, with `--entries` if it's a copy of the passwords file",file_prefix),
+        ErrorKind::MacError=>format!("The {} file failed authentication, it is corrupted or has been tampered with{}",file_prefix,source_error_str),
+        ErrorKind::WrongPassword=>"Too many wrong password attempts".to_string(),
         ErrorKind::EncryptionError=>"An unexpected enryption error has occured".to_string(),
-        ErrorKind::RevertToBackup=>format!("Failed to revert the {} file to the backup{}",file_prefix,source_error_str),
+        ErrorKind::KeyDerivationError=>"An unexpected error has occured while deriving the key from the password".to_string(),
+        ErrorKind::Backup=>format!("Failed to back up the {} file{}",file_prefix,source_error_str),
+        ErrorKind::NoSuchBackup=>"There is no backup with the given id, use `pwm backup list` to list the backups".to_string(),
+        ErrorKind::FileExists=>"The output file already exists, choose a path that doesn't exist".to_string(),
+        ErrorKind::ReadConfig=>format!("Failed to read the config file{}",source_error_str),
+        ErrorKind::InvalidConfig{line}=>format!("Invalid setting on line {} of the config file",line),
+        ErrorKind::ReadKeyFile=>format!("Failed to read the key file{}",source_error_str),
+        ErrorKind::CreateKeyFile=>format!("Failed to create the key file{}",source_error_str),
+        ErrorKind::KeyFileRequired=>format!("The {} file requires a key file, pass it with `--keyfile` or set `keyfile.path` in the config file",file_prefix),
+        ErrorKind::KeyFileAlreadyRequired=>format!("The key slot of the {} file that the password unlocks already requires a key file, remove it with `pwm keyfile remove` first",file_prefix),
+        ErrorKind::KeyFileNotRequired=>format!("The key slot of the {} file that the password unlocks doesn't require a key file",file_prefix),
+        ErrorKind::KeySlotLabelTooLong=>format!("The label of a key slot can't be longer than {} bytes",crate::locker::MAX_KEY_SLOT_LABEL_LENGTH),
+        ErrorKind::TooManyKeySlots=>format!("The {} file already has the maximum of {} key slots, revoke one with `pwm slot revoke` first",file_prefix,crate::locker::MAX_KEY_SLOTS),
+        ErrorKind::NoSuchKeySlot=>format!("The {} file has no key slot with the given id, use `pwm slot list` to list its key slots",file_prefix),
+        ErrorKind::LastKeySlot=>format!("The last key slot of the {} file can't be revoked, since nothing would unlock it",file_prefix),
+        ErrorKind::NoKeySlots=>format!("The {} file was written before key slots were added, run `pwm upgrade` on it first",file_prefix),
+        ErrorKind::WrongDataKey=>format!("The recovered key doesn't unlock the {} file, the shares might be of another file",file_prefix),
+        ErrorKind::InvalidKeyShare=>"The share is invalid, check it for typos".to_string(),
+        ErrorKind::InvalidShareThreshold=>"The threshold can't be larger than the amount of shares".to_string(),
+        ErrorKind::NotEnoughKeyShares=>"Not enough distinct shares were entered to recover the key".to_string(),
+        ErrorKind::KeySharesOfDifferentSplits=>"The shares are of different splits of the key, only shares that were made together can be combined".to_string(),
+        ErrorKind::ReadIdentity=>format!("Failed to read the identity file{}",source_error_str),
+        ErrorKind::CreateIdentity=>format!("Failed to create the identity file{}",source_error_str),
+        ErrorKind::InvalidIdentity=>"The identity file is invalid, it must contain exactly the 32 bytes of a secret key".to_string(),
+        ErrorKind::NoIdentity=>"You have no identity, create one with `pwm identity new`".to_string(),
+        ErrorKind::IdentityExists=>"You already have an identity, see its public key with `pwm identity show`".to_string(),
+        ErrorKind::InvalidPublicKey=>"The public key is invalid, it must be the 64 hex digits that `pwm identity show` prints".to_string(),
+        ErrorKind::NotARecipient=>format!("The {} file can only be unlocked by the identities of its recipients, and yours isn't one of them",file_prefix),
+        ErrorKind::RecipientAlreadyAdded=>format!("The {} file is already encrypted to this recipient",file_prefix),
+        ErrorKind::NoSuchRecipient=>format!("The {} file isn't encrypted to this recipient",file_prefix),
+        ErrorKind::InvalidSharedFile=>"The file wasn't shared with your identity, or it was tampered with".to_string(),
+        ErrorKind::HiddenVaultFull=>format!("The hidden vault of the {} file is full, it can hold at most {} bytes",file_prefix,crate::locker::HIDDEN_VAULT_CAPACITY),
+        ErrorKind::HiddenVaultUnlocked=>format!("Failed to access the key slots of the {} file",file_prefix),
+        ErrorKind::DuressPasswordUnlocksKeySlot=>format!("The duress password must be different from the master password and the passwords of the other key slots of the {} file",file_prefix),
+        ErrorKind::HardenProcess=>format!("Failed to protect the memory of pwm from being dumped or read by other processes{}",source_error_str),
+        ErrorKind::ProcessTraced=>format!("Refusing to unlock the {} file since pwm is being traced by another process, set `debugging.allow = true` in the config file to allow it",file_prefix),
+        ErrorKind::ReplaceFile=>format!("Failed to replace the {} file with its new content{}",file_prefix,source_error_str),
         ErrorKind::PromptPasswordIOError=>format!("An unexpected IO error has occured while trying to prompt the user to enter a password{}",source_error_str),
+        ErrorKind::PromptConfirmationIOError=>format!("An unexpected IO error has occured while trying to prompt the user for confirmation{}",source_error_str),
         ErrorKind::EncodingError=>format!("Failed to decode the {} file as UTF-8{}",file_prefix,source_error_str),
-        ErrorKind::CorruptedFile=>format!("The {} file is corrupted{}",file_prefix,source_error_str),
+        ErrorKind::CorruptedFile{offset}=>format!("The {} file is corrupted at offset {} of its content{}",file_prefix,offset,source_error_str),
         ErrorKind::HomeDir=>format!("Failed to get the path of the current user's home directory{}",source_error_str),
         ErrorKind::EmptyPasswordDict=>format!("The password generation dictionary can't be empty{}",source_error_str),
         ErrorKind::PasswordLengthZero=>format!("The password length can't be 0{}",source_error_str),
         ErrorKind::CopyToClipboard=>format!("Failed to copy to the clipboard{}",source_error_str),
-    };
-    eprintln!("{}", error_style.paint(err));
+        ErrorKind::PasswordsDontMatch=>"The entered passwords don't match".to_string(),
+        ErrorKind::Editor=>format!("Failed to edit the value in the editor{}",source_error_str),
+        ErrorKind::NoMatchingEntry=>format!("No entry in the {} file matches the given filters",file_prefix),
+        ErrorKind::MultipleMatchingEntries=>format!("More than one entry in the {} file matches the given filters, narrow them down to select a single entry",file_prefix),
+    }
+}
+pub fn print_error(error: Error, file_prefix: &str, error_style: &ansi_term::Style) {
+    eprintln!("{}", error_style.paint(error_message(&error, file_prefix)));
 }
@@ -0,0 +1,72 @@
+use crate::locker::{io_to_locker_error, ErrorKind, Result};
+use std::fs::{self, File, OpenOptions};
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+// how long to wait for another pwm process to release the file before giving up
+const LOCK_TIMEOUT: Duration = Duration::from_secs(10);
+const LOCK_RETRY_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Clone, Copy)]
+pub enum LockMode {
+    // for reading the file, any amount of processes may hold it together
+    Shared,
+    // for writing the file, no other process may hold any lock on it
+    Exclusive,
+}
+
+// returns false if another process holds a conflicting lock
+fn try_lock(file: &File, mode: LockMode) -> std::io::Result<bool> {
+    let operation = match mode {
+        LockMode::Shared => libc::LOCK_SH,
+        LockMode::Exclusive => libc::LOCK_EX,
+    };
+    if unsafe { libc::flock(file.as_raw_fd(), operation | libc::LOCK_NB) } == 0 {
+        return Ok(true);
+    }
+    let err = std::io::Error::last_os_error();
+    if err.raw_os_error() == Some(libc::EWOULDBLOCK) {
+        Ok(false)
+    } else {
+        Err(err)
+    }
+}
+
+// the lock is released when the file is closed
+pub fn lock_file(file: &File, mode: LockMode) -> Result<()> {
+    let start = Instant::now();
+    loop {
+        if io_to_locker_error(try_lock(file, mode), ErrorKind::LockFile)? {
+            return Ok(());
+        }
+        if start.elapsed() >= LOCK_TIMEOUT {
+            return Err(ErrorKind::FileLocked.without_source_error());
+        }
+        sleep(LOCK_RETRY_INTERVAL);
+    }
+}
+
+fn is_same_file(file: &File, path: &Path) -> Result<bool> {
+    let file_metadata = io_to_locker_error(file.metadata(), ErrorKind::OpenFile)?;
+    match fs::metadata(path) {
+        Ok(path_metadata) => Ok(file_metadata.dev() == path_metadata.dev()
+            && file_metadata.ino() == path_metadata.ino()),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(err) => Err(ErrorKind::OpenFile.with_source_error(err)),
+    }
+}
+
+// opens the file and locks it. since files are written by renaming a new file over them, the file
+// may have been replaced while waiting for the lock, in which case the new file is opened instead
+pub fn open_locked(path: &Path, open_options: &OpenOptions, mode: LockMode) -> Result<File> {
+    loop {
+        let file = io_to_locker_error(open_options.open(path), ErrorKind::OpenFile)?;
+        lock_file(&file, mode)?;
+        if is_same_file(&file, path)? {
+            return Ok(file);
+        }
+    }
+}
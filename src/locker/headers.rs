@@ -1,87 +1,701 @@
-use crate::locker::{ErrorKind, Result};
+use crate::locker::{encryptor_to_recipient, CipherSuite, Encryptor, ErrorKind, Identity, KdfParams, KeyFile, RecipientKey, Result, DERIVED_KEY_SIZE, KDF_PARAMS_SIZE, X25519_KEY_SIZE};
+use crate::secret::Secret;
 use crate::vec_io::VecReader;
-use chacha20::{ChaCha20,Nonce,cipher::NewStreamCipher};
+use chacha20poly1305::{XChaCha20Poly1305,XNonce,AeadCore};
 use generic_array::GenericArray;
 use sha3::{Digest, Sha3_512};
 use generic_array::typenum::Unsigned;
 use rand::{thread_rng,RngCore};
+use subtle::ConstantTimeEq;
 
 pub type Sha512Digest = GenericArray<u8, <Sha3_512 as Digest>::OutputSize>;
 pub const SALT_LENGTH:usize=16;
 pub const SHA_512_DIGEST_SIZE:usize=<Sha3_512 as Digest>::OutputSize::USIZE;
-pub const CHACHA20_NONCE_SIZE:usize=<ChaCha20 as NewStreamCipher>::NonceSize::USIZE;
-pub const ENCRYPTION_HEADERS_SIZE:usize=
-    SALT_LENGTH + 
-    CHACHA20_NONCE_SIZE+ // nonce
-    SHA_512_DIGEST_SIZE*2;// salted key hash and hmac
+pub const XCHACHA20_NONCE_SIZE:usize=<XChaCha20Poly1305 as AeadCore>::NonceSize::USIZE;
+// the first byte isn't ascii, so that text files are never mistaken for locked files
+pub const MAGIC:[u8;4]=[0x89,b'P',b'W',b'M'];
+// 1: the first versioned format
+// 2: integers in the passwords file are little endian u64s instead of native endian usizes
+// 3: strings in the passwords file are length prefixed instead of nul terminated, and the trash
+//    section is always present
+// 4: the derived key is verified with a key check block instead of a salted hash of it
+// 5: the headers have flags, which can require a key file to unlock the file
+// 6: the content is encrypted with a random data key, which is wrapped by each of the key slots in
+//    the headers with a key derived from the password of the slot
+// 7: the password and additional fields of each entry in the passwords file are sealed on their
+//    own, with a subkey of the data key and a nonce of their own
+// 8: key slots start with their type, and can wrap the data key to the public key of a recipient
+// 9: the encrypted content is followed by a hidden region of a fixed size, which can hold a hidden
+//    vault
+// 10: the headers have the id of the cipher suite that the content is encrypted with
+pub const FORMAT_VERSION:u16=10;
+// the version that files written before the format was versioned are treated as
+pub const UNVERSIONED_FORMAT_VERSION:u16=0;
+pub const FORMAT_HEADER_SIZE:usize=MAGIC.len()+std::mem::size_of::<u16>();
+pub const POLY1305_TAG_SIZE:usize=16;
+// the format version from which the derived key is verified with a key check block instead of a
+// salted hash of the key
+pub const KEY_CHECK_BLOCK_FORMAT_VERSION:u16=4;
+// the format version from which the headers have flags
+pub const HEADER_FLAGS_FORMAT_VERSION:u16=5;
+// the format version from which the content is encrypted with a data key that key slots wrap
+pub const KEY_SLOTS_FORMAT_VERSION:u16=6;
+// the format version from which the content of the passwords file is in the current layout. the
+// entries of older passwords files must be converted before they're written in the current format
+pub const CONTENT_LAYOUT_FORMAT_VERSION:u16=7;
+// the format version from which key slots start with their type, and can be of recipients
+pub const RECIPIENT_KEY_SLOTS_FORMAT_VERSION:u16=8;
+// the format version from which the encrypted content is followed by a hidden region
+pub const HIDDEN_REGION_FORMAT_VERSION:u16=9;
+// the format version from which the headers have the id of the cipher suite, before it the content
+// was always encrypted with xchacha20-poly1305
+pub const CIPHER_SUITES_FORMAT_VERSION:u16=10;
+const PASSWORD_KEY_SLOT_TYPE:u8=0;
+const RECIPIENT_KEY_SLOT_TYPE:u8=1;
+// the key is combined with a key file, which must be given along with the password
+pub const KEY_FILE_REQUIRED_FLAG:u8=1;
+const KNOWN_HEADER_FLAGS:u8=KEY_FILE_REQUIRED_FLAG;
+// every key slot that doesn't match a password costs a key derivation when unlocking the file
+pub const MAX_KEY_SLOTS:usize=16;
+pub const MAX_KEY_SLOT_LABEL_LENGTH:usize=u8::MAX as usize;
+pub const DEFAULT_KEY_SLOT_LABEL:&str="password";
+const KEY_CHECK_BLOCK_SIZE:usize=XCHACHA20_NONCE_SIZE+POLY1305_TAG_SIZE;
+const KEY_CHECK_ASSOCIATED_DATA:&[u8]=b"pwm key check";
+const KEY_SLOT_ASSOCIATED_DATA:&[u8]=b"pwm key slot";
+const RECIPIENT_KEY_SLOT_ASSOCIATED_DATA:&[u8]=b"pwm recipient key slot";
+const WRAPPED_KEY_SIZE:usize=DERIVED_KEY_SIZE+POLY1305_TAG_SIZE;
+// the size of the fields that follow the magic and format version, without the key check. files
+// written before the format was versioned consisted of only these fields followed by the content
+const HEADER_FIELDS_WITHOUT_KEY_CHECK_SIZE:usize=
+    SALT_LENGTH +
+    KDF_PARAMS_SIZE +
+    XCHACHA20_NONCE_SIZE;
 
-pub struct EncryptionHeaders {
-    pub hmac:Sha512Digest,
+// returns the format version of the file, or None if it doesn't start with the magic
+pub fn read_format_version(content:&[u8])->Option<u16>{
+    if content.len()<FORMAT_HEADER_SIZE || content[..MAGIC.len()]!=MAGIC{
+        return None;
+    }
+    Some(u16::from_le_bytes([content[MAGIC.len()],content[MAGIC.len()+1]]))
+}
+
+fn read_flags(reader:&mut VecReader)->std::result::Result<u8,()>{
+    let mut flags=[0u8;1];
+    reader.read_exact(&mut flags)?;
+    // a flag that isn't known might change how the file must be unlocked
+    if flags[0]&!KNOWN_HEADER_FLAGS!=0{
+        return Err(());
+    }
+    Ok(flags[0])
+}
+
+// the key that the content is encrypted with, which the key slots wrap
+pub fn new_data_key()->Secret<[u8;DERIVED_KEY_SIZE]>{
+    let mut data_key=Secret::new([0u8;DERIVED_KEY_SIZE]);
+    thread_rng().fill_bytes(data_key.expose_mut());
+    data_key
+}
+
+// how a key is derived from a password, and whether it's combined with a key file
+#[derive(Clone, Copy)]
+pub struct KeyDerivation {
     pub salt: [u8;SALT_LENGTH],
-    pub salted_key_hash: Sha512Digest,
-    pub nonce: Nonce,
+    pub kdf_params: KdfParams,
+    pub flags: u8,
+}
+impl KeyDerivation {
+    pub fn new(kdf_params:KdfParams,requires_key_file:bool)->KeyDerivation{
+        let mut derivation=KeyDerivation{
+            salt:[0u8;SALT_LENGTH],
+            kdf_params,
+            flags:if requires_key_file{KEY_FILE_REQUIRED_FLAG}else{0},
+        };
+        thread_rng().fill_bytes(&mut derivation.salt);
+        derivation
+    }
+    pub fn requires_key_file(&self)->bool{
+        self.flags&KEY_FILE_REQUIRED_FLAG!=0
+    }
+    pub fn derive_key(&self,password:&[u8],key_file:Option<&KeyFile>)->Result<Secret<[u8;DERIVED_KEY_SIZE]>>{
+        let key_file=match (self.requires_key_file(),key_file){
+            (false,_)=>None,
+            (true,Some(key_file))=>Some(key_file),
+            (true,None)=>return Err(ErrorKind::KeyFileRequired.without_source_error()),
+        };
+        let derived_key=self.kdf_params.derive_key(password,&self.salt)?;
+        match key_file{
+            Some(key_file)=>Ok(key_file.combine(derived_key.expose())),
+            None=>Ok(derived_key),
+        }
+    }
+    fn read(reader:&mut VecReader)->std::result::Result<KeyDerivation,()>{
+        let mut salt=[0u8;SALT_LENGTH];
+        reader.read_exact(&mut salt)?;
+        let kdf_params=KdfParams::read(reader).ok_or(())?;
+        let flags=read_flags(reader)?;
+        Ok(KeyDerivation{salt,kdf_params,flags})
+    }
+    fn write_to(&self,buf:&mut Vec<u8>){
+        buf.extend_from_slice(&self.salt);
+        let mut kdf_params=[0u8;KDF_PARAMS_SIZE];
+        self.kdf_params.write_to(&mut kdf_params);
+        buf.extend_from_slice(&kdf_params);
+        buf.push(self.flags);
+    }
 }
-impl EncryptionHeaders {
-    pub fn new<B:AsRef<[u8]>>(hasher:&mut Sha3_512,content:&[u8],key:B)->EncryptionHeaders{
-        let mut thread_random = thread_rng();
-        let mut result=EncryptionHeaders::default();
-        
-        // generate salt
-        thread_random.fill_bytes(&mut result.salt);
 
-        // calculate hmac
-        hasher.update(content);
-        result.hmac.as_mut().copy_from_slice(&hasher.finalize_reset());
+// how the key that wraps the data key of a key slot is found
+pub enum KeySlotKind {
+    // derived from a password
+    Password(KeyDerivation),
+    // agreed between an ephemeral key pair and the identity of the recipient with the public key
+    Recipient {
+        public_key: RecipientKey,
+        ephemeral_public_key: RecipientKey,
+    },
+}
+
+// one of the ways to unlock a file. the data key that the content is encrypted with is wrapped
+// with the key derived from the password of the slot, or agreed with the identity of its
+// recipient, so slots can be added and revoked without re-encrypting the content
+pub struct KeySlot {
+    pub label: String,
+    pub kind: KeySlotKind,
+    nonce: XNonce,
+    wrapped_key: [u8;WRAPPED_KEY_SIZE],
+}
+impl KeySlot {
+    fn wrap(label:String,kind:KeySlotKind,wrapping_key:&Encryptor,data_key:&[u8])->Result<KeySlot>{
+        if label.len()>MAX_KEY_SLOT_LABEL_LENGTH{
+            return Err(ErrorKind::KeySlotLabelTooLong.without_source_error());
+        }
+        let mut slot=KeySlot{
+            label,
+            kind,
+            nonce:XNonce::default(),
+            wrapped_key:[0u8;WRAPPED_KEY_SIZE],
+        };
+        thread_rng().fill_bytes(&mut slot.nonce);
+        let wrapped_key=wrapping_key.encrypt(&slot.nonce,&slot.associated_data(),data_key)?;
+        slot.wrapped_key.copy_from_slice(&wrapped_key);
+        Ok(slot)
+    }
+    pub fn new(label:String,derivation:KeyDerivation,derived_key:&[u8],data_key:&[u8])->Result<KeySlot>{
+        KeySlot::wrap(label,KeySlotKind::Password(derivation),&Encryptor::new(derived_key),data_key)
+    }
+    // a key slot that only the identity with the given public key can unwrap
+    pub fn to_recipient(label:String,public_key:&RecipientKey,data_key:&[u8])->Result<KeySlot>{
+        let (ephemeral_public_key,wrapping_key)=encryptor_to_recipient(public_key)?;
+        let kind=KeySlotKind::Recipient{
+            public_key:*public_key,
+            ephemeral_public_key,
+        };
+        KeySlot::wrap(label,kind,&wrapping_key,data_key)
+    }
+    // None for the key slots of recipients
+    pub fn derivation(&self)->Option<&KeyDerivation>{
+        match &self.kind{
+            KeySlotKind::Password(derivation)=>Some(derivation),
+            KeySlotKind::Recipient{..}=>None,
+        }
+    }
+    // the public key of the recipient, None for the key slots of passwords
+    pub fn recipient(&self)->Option<&RecipientKey>{
+        match &self.kind{
+            KeySlotKind::Password(_)=>None,
+            KeySlotKind::Recipient{public_key,..}=>Some(public_key),
+        }
+    }
+    pub fn requires_key_file(&self)->bool{
+        self.derivation().is_some_and(|derivation|derivation.requires_key_file())
+    }
+    // the label and key derivation or public keys of the slot are authenticated along with its
+    // wrapped key
+    fn associated_data(&self)->Vec<u8>{
+        let mut buf=match &self.kind{
+            KeySlotKind::Password(_)=>KEY_SLOT_ASSOCIATED_DATA.to_vec(),
+            KeySlotKind::Recipient{..}=>RECIPIENT_KEY_SLOT_ASSOCIATED_DATA.to_vec(),
+        };
+        buf.push(self.label.len() as u8);
+        buf.extend_from_slice(self.label.as_bytes());
+        self.write_kind_fields(&mut buf);
+        buf
+    }
+    fn unwrap_with(&self,wrapping_key:&Encryptor)->Option<Secret<[u8;DERIVED_KEY_SIZE]>>{
+        let unwrapped=Secret::new(
+            wrapping_key.decrypt(&self.nonce,&self.associated_data(),&self.wrapped_key).ok()?
+        );
+        let mut data_key=Secret::new([0u8;DERIVED_KEY_SIZE]);
+        data_key.expose_mut().copy_from_slice(&unwrapped);
+        Some(data_key)
+    }
+    // returns the data key if the derived key is the one that the slot was made with
+    pub fn unwrap_key(&self,derived_key:&[u8])->Option<Secret<[u8;DERIVED_KEY_SIZE]>>{
+        self.unwrap_with(&Encryptor::new(derived_key))
+    }
+    // returns the data key if the slot is of the recipient with the identity
+    pub fn unwrap_key_with_identity(&self,identity:&Identity)->Option<Secret<[u8;DERIVED_KEY_SIZE]>>{
+        match &self.kind{
+            KeySlotKind::Recipient{public_key,ephemeral_public_key}
+                if *public_key==identity.public_key()=>
+            {
+                self.unwrap_with(&identity.decryptor(ephemeral_public_key))
+            }
+            _=>None,
+        }
+    }
+    fn read(reader:&mut VecReader,version:u16)->std::result::Result<KeySlot,()>{
+        let mut label_length=[0u8;1];
+        reader.read_exact(&mut label_length)?;
+        let mut label=vec![0u8;label_length[0] as usize];
+        reader.read_exact(&mut label)?;
+        let mut kind_type=[PASSWORD_KEY_SLOT_TYPE];
+        if version>=RECIPIENT_KEY_SLOTS_FORMAT_VERSION{
+            reader.read_exact(&mut kind_type)?;
+        }
+        let kind=match kind_type[0]{
+            PASSWORD_KEY_SLOT_TYPE=>KeySlotKind::Password(KeyDerivation::read(reader)?),
+            RECIPIENT_KEY_SLOT_TYPE=>{
+                let mut public_key=[0u8;X25519_KEY_SIZE];
+                let mut ephemeral_public_key=[0u8;X25519_KEY_SIZE];
+                reader.read_exact(&mut public_key)?;
+                reader.read_exact(&mut ephemeral_public_key)?;
+                KeySlotKind::Recipient{public_key,ephemeral_public_key}
+            }
+            _=>return Err(()),
+        };
+        let mut slot=KeySlot{
+            label:String::from_utf8(label).map_err(|_|())?,
+            kind,
+            nonce:XNonce::default(),
+            wrapped_key:[0u8;WRAPPED_KEY_SIZE],
+        };
+        reader.read_exact(&mut slot.nonce)?;
+        reader.read_exact(&mut slot.wrapped_key)?;
+        Ok(slot)
+    }
+    fn write_kind_fields(&self,buf:&mut Vec<u8>){
+        match &self.kind{
+            KeySlotKind::Password(derivation)=>derivation.write_to(buf),
+            KeySlotKind::Recipient{public_key,ephemeral_public_key}=>{
+                buf.extend_from_slice(public_key);
+                buf.extend_from_slice(ephemeral_public_key);
+            }
+        }
+    }
+    // key slots are written in the format of the headers, which is the current format whenever
+    // they're written to the file. older formats are only written to find the size of the headers
+    fn write_to(&self,buf:&mut Vec<u8>,version:u16){
+        buf.push(self.label.len() as u8);
+        buf.extend_from_slice(self.label.as_bytes());
+        if version>=RECIPIENT_KEY_SLOTS_FORMAT_VERSION{
+            buf.push(match &self.kind{
+                KeySlotKind::Password(_)=>PASSWORD_KEY_SLOT_TYPE,
+                KeySlotKind::Recipient{..}=>RECIPIENT_KEY_SLOT_TYPE,
+            });
+        }
+        self.write_kind_fields(buf);
+        buf.extend_from_slice(&self.nonce);
+        buf.extend_from_slice(&self.wrapped_key);
+    }
+}
 
-        // salted key hash
-        hasher.update(key.as_ref());
-        hasher.update(result.salt);
-        result.salted_key_hash.as_mut().copy_from_slice(&hasher.finalize_reset());
+// the headers of the current format. only the magic, format version and nonce are authenticated
+// as the associated data of the content, the key slots authenticate themselves, and tampering
+// with them can only make the file impossible to unlock
+pub struct EncryptionHeaders {
+    pub version: u16,
+    pub cipher_suite: CipherSuite,
+    pub key_slots: Vec<KeySlot>,
+    pub nonce: XNonce,
+}
+impl EncryptionHeaders {
+    // returns new headers with a single key slot for the password, along with the random data key
+    // that the slot wraps
+    pub fn new<B:AsRef<[u8]>>(password:B,kdf_params:KdfParams,cipher_suite:CipherSuite)->Result<(EncryptionHeaders,Secret<[u8;DERIVED_KEY_SIZE]>)>{
+        let data_key=new_data_key();
+        let derivation=KeyDerivation::new(kdf_params,false);
+        let derived_key=derivation.derive_key(password.as_ref(),None)?;
+        let key_slot=KeySlot::new(DEFAULT_KEY_SLOT_LABEL.to_string(),derivation,derived_key.expose(),data_key.expose())?;
+        Ok((EncryptionHeaders::with_key_slot(key_slot,cipher_suite),data_key))
+    }
+    pub fn with_key_slot(key_slot:KeySlot,cipher_suite:CipherSuite)->EncryptionHeaders{
+        let mut headers=EncryptionHeaders{
+            version:FORMAT_VERSION,
+            cipher_suite,
+            key_slots:vec![key_slot],
+            nonce:XNonce::default(),
+        };
+        thread_rng().fill_bytes(&mut headers.nonce);
+        headers
+    }
+    // a key file must be given to unlock the file with a password if every key slot of a
+    // password requires one. a file without any such slots can't be unlocked with a password at
+    // all, so it doesn't require a key file either
+    pub fn requires_key_file(&self)->bool{
+        self.has_password_key_slots()
+            && self.key_slots.iter().filter_map(KeySlot::derivation).all(KeyDerivation::requires_key_file)
+    }
+    pub fn uses_key_file(&self)->bool{
+        self.key_slots.iter().any(KeySlot::requires_key_file)
+    }
+    pub fn has_password_key_slots(&self)->bool{
+        self.key_slots.iter().any(|slot|slot.derivation().is_some())
+    }
+    // reads the fields of the headers, which come after the magic and format version
+    pub fn read(reader:&mut VecReader,version:u16) -> Result<EncryptionHeaders> {
+        let cipher_suite=if version>=CIPHER_SUITES_FORMAT_VERSION{
+            let mut cipher_suite_id=[0u8;1];
+            if reader.read_exact(&mut cipher_suite_id).is_err(){
+                return Err(ErrorKind::FileNotEncryptedProperly.without_source_error());
+            }
+            match CipherSuite::from_id(cipher_suite_id[0]){
+                Some(cipher_suite)=>cipher_suite,
+                None=>return Err(ErrorKind::UnsupportedCipherSuite.without_source_error()),
+            }
+        }else{
+            CipherSuite::XChaCha20Poly1305
+        };
+        fn read_with_unit_error(reader:&mut VecReader,version:u16,cipher_suite:CipherSuite)->std::result::Result<EncryptionHeaders,()>{
+            let mut key_slots_amount=[0u8;1];
+            reader.read_exact(&mut key_slots_amount)?;
+            let key_slots_amount=key_slots_amount[0] as usize;
+            if key_slots_amount==0 || key_slots_amount>MAX_KEY_SLOTS{
+                return Err(());
+            }
+            let key_slots=(0..key_slots_amount)
+                .map(|_|KeySlot::read(reader,version))
+                .collect::<std::result::Result<Vec<KeySlot>,()>>()?;
+            let mut nonce=XNonce::default();
+            reader.read_exact(&mut nonce)?;
+            Ok(EncryptionHeaders{version,cipher_suite,key_slots,nonce})
+        }
+        match read_with_unit_error(reader,version,cipher_suite){
+            Ok(headers)=>Ok(headers),
+            Err(())=>Err(ErrorKind::FileNotEncryptedProperly.without_source_error())
+        }
+    }
+    pub fn to_bytes(&self)->Vec<u8>{
+        let mut buf=Vec::new();
+        buf.extend_from_slice(&MAGIC);
+        buf.extend_from_slice(&self.version.to_le_bytes());
+        if self.version>=CIPHER_SUITES_FORMAT_VERSION{
+            buf.push(self.cipher_suite.id());
+        }
+        buf.push(self.key_slots.len() as u8);
+        for key_slot in &self.key_slots{
+            key_slot.write_to(&mut buf,self.version);
+        }
+        buf.extend_from_slice(&self.nonce);
+        buf
+    }
+    // the size of the whole headers, which the content follows
+    pub fn size(&self)->usize{
+        self.to_bytes().len()
+    }
+    pub fn associated_data(&self)->Vec<u8>{
+        let mut buf=Vec::with_capacity(FORMAT_HEADER_SIZE+1+XCHACHA20_NONCE_SIZE);
+        buf.extend_from_slice(&MAGIC);
+        buf.extend_from_slice(&self.version.to_le_bytes());
+        if self.version>=CIPHER_SUITES_FORMAT_VERSION{
+            buf.push(self.cipher_suite.id());
+        }
+        buf.extend_from_slice(&self.nonce);
+        buf
+    }
+}
 
-        thread_random.fill_bytes(&mut result.nonce);
+// how a key derived from a password is verified before the content is decrypted with it
+pub enum KeyCheck {
+    // a hash of the derived key and the salt, used before the key check block
+    SaltedHash(Sha512Digest),
+    // the tag of an empty message encrypted with the derived key, so verifying it is exactly as
+    // expensive as trying to decrypt the content, and its tag is compared in constant time
+    Block{
+        nonce: XNonce,
+        tag: [u8;POLY1305_TAG_SIZE],
+    },
+}
+impl KeyCheck {
+    fn size(&self)->usize{
+        match self{
+            KeyCheck::SaltedHash(_)=>SHA_512_DIGEST_SIZE,
+            KeyCheck::Block{..}=>KEY_CHECK_BLOCK_SIZE,
+        }
+    }
+    fn read(reader:&mut VecReader,version:u16)->std::result::Result<KeyCheck,()>{
+        if version<KEY_CHECK_BLOCK_FORMAT_VERSION{
+            let mut salted_key_hash=Sha512Digest::default();
+            reader.read_exact(&mut salted_key_hash)?;
+            return Ok(KeyCheck::SaltedHash(salted_key_hash));
+        }
+        let mut nonce=XNonce::default();
+        let mut tag=[0u8;POLY1305_TAG_SIZE];
+        reader.read_exact(&mut nonce)?;
+        reader.read_exact(&mut tag)?;
+        Ok(KeyCheck::Block{nonce,tag})
+    }
+    fn write_to(&self,buf:&mut Vec<u8>){
+        match self{
+            KeyCheck::SaltedHash(salted_key_hash)=>buf.extend_from_slice(salted_key_hash),
+            KeyCheck::Block{nonce,tag}=>{
+                buf.extend_from_slice(nonce);
+                buf.extend_from_slice(tag);
+            }
+        }
+    }
+}
 
-        result
+// the headers of the formats before key slots, in which the content is encrypted with the key
+// derived from the password. they are only ever read, the file is written with a key slot that
+// wraps a new data key with the same derived key. the whole headers, including the magic and
+// format version, are authenticated as the associated data of the content
+pub struct DerivedKeyHeaders {
+    pub version: u16,
+    pub salt: [u8;SALT_LENGTH],
+    pub key_check: KeyCheck,
+    pub kdf_params: KdfParams,
+    pub flags: u8,
+    pub nonce: XNonce,
+}
+impl DerivedKeyHeaders {
+    pub fn derivation(&self)->KeyDerivation{
+        KeyDerivation{
+            salt:self.salt,
+            kdf_params:self.kdf_params,
+            flags:self.flags,
+        }
     }
-    pub fn size()->usize{
-       ENCRYPTION_HEADERS_SIZE
+    pub fn test_derived_key(&self,derived_key:&[u8])->bool{
+        match &self.key_check{
+            KeyCheck::SaltedHash(salted_key_hash)=>{
+                let mut hasher=Sha3_512::new();
+                hasher.update(derived_key);
+                hasher.update(self.salt);
+                bool::from(hasher.finalize().as_slice().ct_eq(salted_key_hash.as_slice()))
+            }
+            KeyCheck::Block{nonce,tag}=>{
+                Encryptor::new(derived_key).decrypt(nonce,KEY_CHECK_ASSOCIATED_DATA,tag).is_ok()
+            }
+        }
     }
-    pub fn read(reader:&mut VecReader) -> Result<EncryptionHeaders> {
+    // reads the fields of the headers, which come after the magic and format version
+    pub fn read(reader:&mut VecReader,version:u16) -> Result<DerivedKeyHeaders> {
         fn read_with_unit_error(
             reader: &mut VecReader,
-            headers: &mut EncryptionHeaders,
+            headers: &mut DerivedKeyHeaders,
         ) -> std::result::Result<(),()> {
-            reader.read_exact(&mut headers.hmac)?;
-            reader.read_exact(&mut headers.nonce)?;
             reader.read_exact(&mut headers.salt)?;
-            reader.read_exact(&mut headers.salted_key_hash)
+            headers.key_check=KeyCheck::read(reader,headers.version)?;
+            headers.kdf_params=KdfParams::read(reader).ok_or(())?;
+            if headers.version>=HEADER_FLAGS_FORMAT_VERSION{
+                headers.flags=read_flags(reader)?;
+            }
+            reader.read_exact(&mut headers.nonce)
         }
-        let mut headers=EncryptionHeaders::default();
+        let mut headers=DerivedKeyHeaders{
+            version,
+            salt: [0u8;SALT_LENGTH],
+            key_check: KeyCheck::SaltedHash(Sha512Digest::default()),
+            kdf_params: KdfParams::DEFAULT,
+            flags: 0,
+            nonce: XNonce::default(),
+        };
         match read_with_unit_error(reader,&mut headers){
             Ok(())=>Ok(headers),
             Err(())=>Err(ErrorKind::FileNotEncryptedProperly.without_source_error())
         }
     }
-    pub fn write_to(&self,buf:&mut [u8]){
-        let mut current_index=0;
-        buf[..SHA_512_DIGEST_SIZE].copy_from_slice(&self.hmac);
-        current_index+=SHA_512_DIGEST_SIZE;
-        
-        buf[current_index..current_index+CHACHA20_NONCE_SIZE].copy_from_slice(&self.nonce);
-        current_index+=CHACHA20_NONCE_SIZE;
-        
-        buf[current_index..current_index+SALT_LENGTH].copy_from_slice(&self.salt);
-        current_index+=SALT_LENGTH;
-
-        buf[current_index..current_index+SHA_512_DIGEST_SIZE].copy_from_slice(&self.salted_key_hash);
+    pub fn fields_size(&self)->usize{
+        let flags_size=if self.version>=HEADER_FLAGS_FORMAT_VERSION{1}else{0};
+        HEADER_FIELDS_WITHOUT_KEY_CHECK_SIZE+self.key_check.size()+flags_size
+    }
+    // the size of the whole headers, which the content follows
+    pub fn size(&self)->usize{
+        FORMAT_HEADER_SIZE+self.fields_size()
+    }
+    pub fn fields_to_bytes(&self)->Vec<u8>{
+        let mut buf=Vec::with_capacity(self.fields_size());
+        buf.extend_from_slice(&self.salt);
+        self.key_check.write_to(&mut buf);
+        let mut kdf_params=[0u8;KDF_PARAMS_SIZE];
+        self.kdf_params.write_to(&mut kdf_params);
+        buf.extend_from_slice(&kdf_params);
+        if self.version>=HEADER_FLAGS_FORMAT_VERSION{
+            buf.push(self.flags);
+        }
+        buf.extend_from_slice(&self.nonce);
+        buf
+    }
+    pub fn to_bytes(&self)->Vec<u8>{
+        let mut buf=Vec::with_capacity(self.size());
+        buf.extend_from_slice(&MAGIC);
+        buf.extend_from_slice(&self.version.to_le_bytes());
+        buf.extend_from_slice(&self.fields_to_bytes());
+        buf
     }
 }
-impl Default for EncryptionHeaders{
-    fn default() -> Self {
-        EncryptionHeaders {
-            hmac:Sha512Digest::default(),
-            salt: [0u8;SALT_LENGTH],
-            salted_key_hash: Sha512Digest::default(),
-            nonce: Nonce::default(),
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use x25519_dalek::{PublicKey,StaticSecret};
+
+    const KDF_PARAMS:KdfParams=KdfParams{memory_kib:8,iterations:1,parallelism:1};
+    const PASSWORD:&[u8]=b"correct horse battery staple";
+
+    fn password_key_slot(label:&str,requires_key_file:bool,data_key:&[u8])->KeySlot{
+        let derivation=KeyDerivation::new(KDF_PARAMS,requires_key_file);
+        // the key file is only recorded in the flags, the slot itself doesn't care what it wraps
+        // the data key with
+        let derived_key=KDF_PARAMS.derive_key(PASSWORD,&derivation.salt).unwrap();
+        KeySlot::new(label.to_string(),derivation,derived_key.expose(),data_key).unwrap()
+    }
+    fn recipient_key_slot(label:&str,data_key:&[u8])->KeySlot{
+        let mut secret=[0u8;X25519_KEY_SIZE];
+        thread_rng().fill_bytes(&mut secret);
+        let public_key=PublicKey::from(&StaticSecret::from(secret)).to_bytes();
+        KeySlot::to_recipient(label.to_string(),&public_key,data_key).unwrap()
+    }
+    // headers with a slot of each kind that the format version supports
+    fn headers_of_version(version:u16)->(EncryptionHeaders,Secret<[u8;DERIVED_KEY_SIZE]>){
+        let (mut headers,data_key)=EncryptionHeaders::new(PASSWORD,KDF_PARAMS,CipherSuite::Aes256GcmSiv).unwrap();
+        headers.version=version;
+        if version<CIPHER_SUITES_FORMAT_VERSION{
+            headers.cipher_suite=CipherSuite::XChaCha20Poly1305;
+        }
+        headers.key_slots.push(password_key_slot("laptop",true,data_key.expose()));
+        if version>=RECIPIENT_KEY_SLOTS_FORMAT_VERSION{
+            headers.key_slots.push(recipient_key_slot("alice",data_key.expose()));
+        }
+        (headers,data_key)
+    }
+    fn read_headers(bytes:&[u8])->Result<EncryptionHeaders>{
+        let version=read_format_version(bytes).unwrap();
+        EncryptionHeaders::read(&mut VecReader::new(bytes[FORMAT_HEADER_SIZE..].to_vec()),version)
+    }
+    fn is_not_encrypted_properly(result:Result<EncryptionHeaders>)->bool{
+        result.is_err_and(|err|matches!(err.kind(),ErrorKind::FileNotEncryptedProperly))
+    }
+
+    #[test]
+    fn headers_round_trip_in_each_format(){
+        for version in KEY_SLOTS_FORMAT_VERSION..=FORMAT_VERSION{
+            let (headers,data_key)=headers_of_version(version);
+            let bytes=headers.to_bytes();
+            assert_eq!(bytes.len(),headers.size());
+            let read=read_headers(&bytes).unwrap();
+            assert_eq!(read.version,version);
+            assert_eq!(read.cipher_suite,headers.cipher_suite);
+            assert_eq!(read.nonce,headers.nonce);
+            assert_eq!(read.to_bytes(),bytes);
+            let labels=read.key_slots.iter().map(|slot|slot.label.as_str()).collect::<Vec<&str>>();
+            let expected_labels=headers.key_slots.iter().map(|slot|slot.label.as_str()).collect::<Vec<&str>>();
+            assert_eq!(labels,expected_labels);
+            // the slot of the password still unwraps the data key
+            let derivation=read.key_slots[0].derivation().unwrap();
+            let derived_key=derivation.derive_key(PASSWORD,None).unwrap();
+            let unwrapped=read.key_slots[0].unwrap_key(derived_key.expose()).unwrap();
+            assert_eq!(unwrapped.expose(),data_key.expose());
+        }
+    }
+
+    #[test]
+    fn key_slot_round_trips(){
+        let data_key=new_data_key();
+        let slots=[
+            password_key_slot(DEFAULT_KEY_SLOT_LABEL,false,data_key.expose()),
+            password_key_slot(&"l".repeat(MAX_KEY_SLOT_LABEL_LENGTH),true,data_key.expose()),
+            recipient_key_slot("",data_key.expose()),
+        ];
+        for slot in &slots{
+            for version in [KEY_SLOTS_FORMAT_VERSION,FORMAT_VERSION]{
+                if slot.recipient().is_some() && version<RECIPIENT_KEY_SLOTS_FORMAT_VERSION{
+                    continue;
+                }
+                let mut bytes=Vec::new();
+                slot.write_to(&mut bytes,version);
+                let mut reader=VecReader::new(bytes.clone());
+                let read=KeySlot::read(&mut reader,version).unwrap();
+                assert!(reader.eof());
+                assert_eq!(read.label,slot.label);
+                assert_eq!(read.requires_key_file(),slot.requires_key_file());
+                assert_eq!(read.recipient(),slot.recipient());
+                let mut rewritten=Vec::new();
+                read.write_to(&mut rewritten,version);
+                assert_eq!(rewritten,bytes);
+            }
+        }
+    }
+
+    #[test]
+    fn too_long_label_is_rejected(){
+        let derivation=KeyDerivation::new(KDF_PARAMS,false);
+        let label="l".repeat(MAX_KEY_SLOT_LABEL_LENGTH+1);
+        let err=KeySlot::new(label,derivation,&[0u8;DERIVED_KEY_SIZE],&[0u8;DERIVED_KEY_SIZE]).err().unwrap();
+        assert!(matches!(err.kind(),ErrorKind::KeySlotLabelTooLong));
+    }
+
+    #[test]
+    fn truncated_headers_are_rejected(){
+        for version in KEY_SLOTS_FORMAT_VERSION..=FORMAT_VERSION{
+            let bytes=headers_of_version(version).0.to_bytes();
+            for length in FORMAT_HEADER_SIZE..bytes.len(){
+                assert!(is_not_encrypted_properly(read_headers(&bytes[..length])),"version {} truncated to {} bytes",version,length);
+            }
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn malformed_headers_are_rejected(){
+        let (headers,_)=headers_of_version(FORMAT_VERSION);
+        let bytes=headers.to_bytes();
+        let cipher_suite_offset=FORMAT_HEADER_SIZE;
+        let key_slots_amount_offset=cipher_suite_offset+1;
+        let label_offset=key_slots_amount_offset+2;
+        let type_offset=label_offset+DEFAULT_KEY_SLOT_LABEL.len();
+        let flags_offset=type_offset+1+SALT_LENGTH+KDF_PARAMS_SIZE;
+        let with_byte=|offset:usize,byte:u8|{
+            let mut malformed=bytes.clone();
+            malformed[offset]=byte;
+            malformed
+        };
+        let err=read_headers(&with_byte(cipher_suite_offset,0xff)).err().unwrap();
+        assert!(matches!(err.kind(),ErrorKind::UnsupportedCipherSuite));
+        for malformed in [
+            // no key slots at all, or more than can be written
+            with_byte(key_slots_amount_offset,0),
+            with_byte(key_slots_amount_offset,MAX_KEY_SLOTS as u8+1),
+            // more key slots than there are
+            with_byte(key_slots_amount_offset,headers.key_slots.len() as u8+1),
+            // a label that isn't utf-8
+            with_byte(label_offset,0xff),
+            // a type of key slot or a flag that a newer version might have added
+            with_byte(type_offset,0xff),
+            with_byte(flags_offset,KEY_FILE_REQUIRED_FLAG<<1),
+        ]{
+            assert!(is_not_encrypted_properly(read_headers(&malformed)));
+        }
+    }
+
+    #[test]
+    fn key_file_is_required_only_if_every_password_requires_one(){
+        let data_key=new_data_key();
+        let headers_with=|key_slots:Vec<KeySlot>|EncryptionHeaders{
+            version:FORMAT_VERSION,
+            cipher_suite:CipherSuite::DEFAULT,
+            key_slots,
+            nonce:XNonce::default(),
+        };
+        let headers=headers_with(vec![password_key_slot("a",true,data_key.expose()),password_key_slot("b",false,data_key.expose())]);
+        assert!(!headers.requires_key_file());
+        assert!(headers.uses_key_file());
+        let headers=headers_with(vec![password_key_slot("a",true,data_key.expose()),recipient_key_slot("b",data_key.expose())]);
+        assert!(headers.requires_key_file());
+        // a file that only recipients can unlock is never unlocked with a password
+        let headers=headers_with(vec![recipient_key_slot("a",data_key.expose())]);
+        assert!(!headers.has_password_key_slots());
+        assert!(!headers.requires_key_file());
+        assert!(!headers.uses_key_file());
+    }
+}
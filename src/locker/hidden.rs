@@ -0,0 +1,102 @@
+use crate::locker::{
+    Encryptor, ErrorKind, KdfParams, Result, FORMAT_VERSION, POLY1305_TAG_SIZE, SALT_LENGTH,
+    XCHACHA20_NONCE_SIZE,
+};
+use chacha20poly1305::XNonce;
+use rand::{thread_rng, RngCore};
+use std::convert::TryInto;
+
+// every file from the hidden region format version ends with a region of this size, which is
+// random bytes unless a hidden vault was written to it. a hidden vault is encrypted to look just
+// like them, so the region is there whether or not a hidden vault is
+pub const HIDDEN_REGION_SIZE: usize = 16 * 1024;
+const HIDDEN_VAULT_ASSOCIATED_DATA: &[u8] = b"pwm hidden vault";
+// nothing that tells a hidden vault apart from random bytes can be stored in the file, so the
+// key of every hidden vault is derived with the same params
+const HIDDEN_VAULT_KDF_PARAMS: KdfParams = KdfParams {
+    memory_kib: 64 * 1024,
+    iterations: 3,
+    parallelism: 1,
+};
+// the region is the salt, the nonce, and the encrypted format version, length and content of the
+// hidden vault, padded with zeros to fill the region
+const HIDDEN_VAULT_HEADERS_SIZE: usize = SALT_LENGTH + XCHACHA20_NONCE_SIZE;
+const HIDDEN_VAULT_PADDED_SIZE: usize =
+    HIDDEN_REGION_SIZE - HIDDEN_VAULT_HEADERS_SIZE - POLY1305_TAG_SIZE;
+const HIDDEN_VAULT_LENGTH_SIZE: usize = std::mem::size_of::<u16>() + std::mem::size_of::<u64>();
+pub const HIDDEN_VAULT_CAPACITY: usize = HIDDEN_VAULT_PADDED_SIZE - HIDDEN_VAULT_LENGTH_SIZE;
+
+pub fn random_hidden_region() -> Vec<u8> {
+    let mut region = vec![0u8; HIDDEN_REGION_SIZE];
+    thread_rng().fill_bytes(&mut region);
+    region
+}
+
+// a hidden vault that was unlocked with its password, whose content is encrypted with the derived
+// key itself since it has no key slots
+pub struct HiddenVault {
+    salt: [u8; SALT_LENGTH],
+    // the format version of the content of the hidden vault, which is independent of the version
+    // of the file
+    pub version: u16,
+}
+impl HiddenVault {
+    // a new empty hidden vault, which replaces whatever is in the region once it's written
+    pub fn new(password: &[u8]) -> Result<(HiddenVault, Encryptor)> {
+        let mut salt = [0u8; SALT_LENGTH];
+        thread_rng().fill_bytes(&mut salt);
+        let derived_key = HIDDEN_VAULT_KDF_PARAMS.derive_key(password, &salt)?;
+        let hidden_vault = HiddenVault {
+            salt,
+            version: FORMAT_VERSION,
+        };
+        Ok((hidden_vault, Encryptor::new(derived_key)))
+    }
+    // tries the password as the password of a hidden vault in the region, which fails the same
+    // way whether the region is random bytes or a hidden vault of another password
+    pub fn open(region: &[u8], password: &[u8]) -> Option<(HiddenVault, Encryptor, Vec<u8>)> {
+        let (headers, encrypted) = region.split_at(HIDDEN_VAULT_HEADERS_SIZE);
+        let (salt, nonce) = headers.split_at(SALT_LENGTH);
+        let derived_key = HIDDEN_VAULT_KDF_PARAMS.derive_key(password, salt).ok()?;
+        let encryptor = Encryptor::new(derived_key);
+        let padded = encryptor
+            .decrypt(
+                XNonce::from_slice(nonce),
+                HIDDEN_VAULT_ASSOCIATED_DATA,
+                encrypted,
+            )
+            .ok()?;
+        let (version, rest) = padded.split_at(std::mem::size_of::<u16>());
+        let (length, rest) = rest.split_at(std::mem::size_of::<u64>());
+        let length = u64::from_le_bytes(length.try_into().ok()?) as usize;
+        if length > rest.len() {
+            return None;
+        }
+        let hidden_vault = HiddenVault {
+            salt: salt.try_into().ok()?,
+            version: u16::from_le_bytes(version.try_into().ok()?),
+        };
+        Some((hidden_vault, encryptor, rest[..length].to_vec()))
+    }
+    // encrypts the content, which must be in the current format, into a new region with a new
+    // nonce
+    pub fn seal(&mut self, encryptor: &Encryptor, content: &[u8]) -> Result<Vec<u8>> {
+        if content.len() > HIDDEN_VAULT_CAPACITY {
+            return Err(ErrorKind::HiddenVaultFull.without_source_error());
+        }
+        let mut padded = Vec::with_capacity(HIDDEN_VAULT_PADDED_SIZE);
+        padded.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        padded.extend_from_slice(&(content.len() as u64).to_le_bytes());
+        padded.extend_from_slice(content);
+        padded.resize(HIDDEN_VAULT_PADDED_SIZE, 0);
+        let mut nonce = XNonce::default();
+        thread_rng().fill_bytes(&mut nonce);
+        let encrypted = encryptor.encrypt(&nonce, HIDDEN_VAULT_ASSOCIATED_DATA, &padded)?;
+        let mut region = Vec::with_capacity(HIDDEN_REGION_SIZE);
+        region.extend_from_slice(&self.salt);
+        region.extend_from_slice(&nonce);
+        region.extend_from_slice(&encrypted);
+        self.version = FORMAT_VERSION;
+        Ok(region)
+    }
+}
@@ -0,0 +1,197 @@
+use crate::locker::{
+    io_to_locker_error, Encryptor, ErrorKind, Result, SealedReader, DERIVED_KEY_SIZE,
+    FORMAT_VERSION, PRIVATE_FILE_MODE, XCHACHA20_NONCE_SIZE,
+};
+use crate::secret::Secret;
+use crate::vec_io::VecReader;
+use chacha20poly1305::XNonce;
+use rand::{thread_rng, RngCore};
+use sha3::{Digest, Sha3_256};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+use x25519_dalek::{PublicKey, StaticSecret};
+
+pub const X25519_KEY_SIZE: usize = 32;
+const RECIPIENT_KEY_CONTEXT: &[u8] = b"pwm recipient key";
+// files that are shared with a recipient start with their own magic, so that they're never
+// mistaken for locked files
+const SHARED_FILE_MAGIC: [u8; 4] = [0x89, b'P', b'W', b'S'];
+const SHARED_FILE_FORMAT_VERSION: u16 = 1;
+const SHARED_FILE_HEADERS_SIZE: usize =
+    SHARED_FILE_MAGIC.len() + std::mem::size_of::<u16>() + X25519_KEY_SIZE + XCHACHA20_NONCE_SIZE;
+
+pub type RecipientKey = [u8; X25519_KEY_SIZE];
+
+// the key that something encrypted to a recipient is encrypted with, agreed between an ephemeral
+// key pair and the key pair of the recipient. both public keys are hashed into it, so it's bound
+// to this exact pair
+fn recipient_encryptor(
+    shared_secret: &[u8],
+    ephemeral_public_key: &RecipientKey,
+    recipient: &RecipientKey,
+) -> Encryptor {
+    let mut hasher = Sha3_256::new();
+    hasher.update(RECIPIENT_KEY_CONTEXT);
+    hasher.update(shared_secret);
+    hasher.update(ephemeral_public_key);
+    hasher.update(recipient);
+    let mut key = Secret::new([0u8; DERIVED_KEY_SIZE]);
+    key.expose_mut().copy_from_slice(&hasher.finalize());
+    Encryptor::new(key)
+}
+
+// returns the public key of a new ephemeral key pair, along with the encryptor that encrypts to
+// the recipient, which the recipient can only derive again with its identity
+pub fn encryptor_to_recipient(recipient: &RecipientKey) -> Result<(RecipientKey, Encryptor)> {
+    let mut ephemeral_secret_bytes = Secret::new([0u8; X25519_KEY_SIZE]);
+    thread_rng().fill_bytes(ephemeral_secret_bytes.expose_mut());
+    let ephemeral_secret = StaticSecret::from(*ephemeral_secret_bytes.expose());
+    let ephemeral_public_key = PublicKey::from(&ephemeral_secret).to_bytes();
+    let shared_secret = ephemeral_secret.diffie_hellman(&PublicKey::from(*recipient));
+    // a public key of low order makes the shared secret known to anyone
+    if !shared_secret.was_contributory() {
+        return Err(ErrorKind::InvalidPublicKey.without_source_error());
+    }
+    let encryptor =
+        recipient_encryptor(shared_secret.as_bytes(), &ephemeral_public_key, recipient);
+    Ok((ephemeral_public_key, encryptor))
+}
+
+// the magic, format version and ephemeral public key of a shared file, which are authenticated
+// along with its content
+fn shared_file_associated_data(ephemeral_public_key: &RecipientKey) -> Vec<u8> {
+    let mut associated_data = SHARED_FILE_MAGIC.to_vec();
+    associated_data.extend_from_slice(&SHARED_FILE_FORMAT_VERSION.to_le_bytes());
+    associated_data.extend_from_slice(ephemeral_public_key);
+    associated_data
+}
+
+// writes the content to a new file at the given path, which mustn't exist, encrypted to the
+// recipient. the file consists of the associated data, the nonce and the encrypted content
+pub fn write_shared_file<P: AsRef<Path>>(
+    path: P,
+    recipient: &RecipientKey,
+    content: &[u8],
+) -> Result<()> {
+    let (ephemeral_public_key, encryptor) = encryptor_to_recipient(recipient)?;
+    let mut shared_file = shared_file_associated_data(&ephemeral_public_key);
+    let mut nonce = XNonce::default();
+    thread_rng().fill_bytes(&mut nonce);
+    let encrypted = encryptor.encrypt(&nonce, &shared_file, content)?;
+    shared_file.extend_from_slice(&nonce);
+    shared_file.extend_from_slice(&encrypted);
+    let mut file = match OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(PRIVATE_FILE_MODE)
+        .open(path)
+    {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+            return Err(ErrorKind::FileExists.without_source_error())
+        }
+        Err(e) => return Err(ErrorKind::WriteFile.with_source_error(e)),
+    };
+    io_to_locker_error(file.write_all(&shared_file), ErrorKind::WriteFile)?;
+    io_to_locker_error(file.sync_all(), ErrorKind::WriteFile)
+}
+
+pub fn public_key_to_hex(public_key: &RecipientKey) -> String {
+    public_key.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+pub fn public_key_from_hex(hex: &str) -> Result<RecipientKey> {
+    let invalid_public_key = || ErrorKind::InvalidPublicKey.without_source_error();
+    let hex = hex.trim();
+    if hex.len() != X25519_KEY_SIZE * 2 || !hex.is_ascii() {
+        return Err(invalid_public_key());
+    }
+    let mut public_key = [0u8; X25519_KEY_SIZE];
+    for (index, byte) in public_key.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[index * 2..index * 2 + 2], 16)
+            .map_err(|_| invalid_public_key())?;
+    }
+    Ok(public_key)
+}
+
+// the x25519 key pair of a user, which files and entries can be encrypted to by its public key.
+// the identity file holds the secret key, so like a key file it must be kept private
+pub struct Identity {
+    secret: StaticSecret,
+}
+impl Identity {
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Identity> {
+        let content = Secret::new(io_to_locker_error(
+            std::fs::read(path),
+            ErrorKind::ReadIdentity,
+        )?);
+        if content.len() != X25519_KEY_SIZE {
+            return Err(ErrorKind::InvalidIdentity.without_source_error());
+        }
+        let mut secret = Secret::new([0u8; X25519_KEY_SIZE]);
+        secret.expose_mut().copy_from_slice(&content);
+        Ok(Identity {
+            secret: StaticSecret::from(*secret.expose()),
+        })
+    }
+    // writes a new identity at the given path, which mustn't exist
+    pub fn generate<P: AsRef<Path>>(path: P) -> Result<Identity> {
+        let mut secret = Secret::new([0u8; X25519_KEY_SIZE]);
+        thread_rng().fill_bytes(secret.expose_mut());
+        if let Some(dir) = path.as_ref().parent() {
+            io_to_locker_error(std::fs::create_dir_all(dir), ErrorKind::CreateIdentity)?;
+        }
+        let mut file = io_to_locker_error(
+            OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .mode(PRIVATE_FILE_MODE)
+                .open(path),
+            ErrorKind::CreateIdentity,
+        )?;
+        io_to_locker_error(file.write_all(secret.expose()), ErrorKind::CreateIdentity)?;
+        io_to_locker_error(file.sync_all(), ErrorKind::CreateIdentity)?;
+        Ok(Identity {
+            secret: StaticSecret::from(*secret.expose()),
+        })
+    }
+    pub fn public_key(&self) -> RecipientKey {
+        PublicKey::from(&self.secret).to_bytes()
+    }
+    // decrypts a file that was shared with this identity, and returns a reader of its content
+    pub fn read_shared_file<P: AsRef<Path>>(&self, path: P) -> Result<SealedReader> {
+        let shared_file = io_to_locker_error(std::fs::read(path), ErrorKind::ReadFile)?;
+        let invalid_shared_file = || ErrorKind::InvalidSharedFile.without_source_error();
+        if shared_file.len() < SHARED_FILE_HEADERS_SIZE
+            || shared_file[..SHARED_FILE_MAGIC.len()] != SHARED_FILE_MAGIC
+        {
+            return Err(invalid_shared_file());
+        }
+        let (headers, encrypted) = shared_file.split_at(SHARED_FILE_HEADERS_SIZE);
+        let (associated_data, nonce) = headers.split_at(SHARED_FILE_HEADERS_SIZE - XCHACHA20_NONCE_SIZE);
+        let mut ephemeral_public_key = [0u8; X25519_KEY_SIZE];
+        ephemeral_public_key.copy_from_slice(&associated_data[associated_data.len() - X25519_KEY_SIZE..]);
+        if associated_data != shared_file_associated_data(&ephemeral_public_key).as_slice() {
+            return Err(ErrorKind::UnsupportedFormatVersion.without_source_error());
+        }
+        let content = self
+            .decryptor(&ephemeral_public_key)
+            .decrypt(XNonce::from_slice(nonce), associated_data, encrypted)
+            .map_err(|_| invalid_shared_file())?;
+        Ok(SealedReader::new(VecReader::new(content), FORMAT_VERSION))
+    }
+    // the encryptor of something that was encrypted to the public key of this identity, with the
+    // ephemeral key pair of the given public key
+    pub fn decryptor(&self, ephemeral_public_key: &RecipientKey) -> Encryptor {
+        let shared_secret = self
+            .secret
+            .diffie_hellman(&PublicKey::from(*ephemeral_public_key));
+        recipient_encryptor(
+            shared_secret.as_bytes(),
+            ephemeral_public_key,
+            &self.public_key(),
+        )
+    }
+}
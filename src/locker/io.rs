@@ -1,13 +1,35 @@
-use crate::locker::Result;
+use crate::locker::{Result, Sealed, SealedReader};
+use crate::secret::Secret;
 
 pub trait EncryptedRead {
     fn read_exact(&mut self, buf: &mut [u8]) -> Result<()>;
-    fn read_until(&mut self, terminator: u8) -> Vec<u8>;
+    // reads the given amount of bytes, failing without allocating them if there aren't enough left
+    fn read_bytes(&mut self, amount: u64) -> Result<Vec<u8>>;
+    // reads up to the terminator and consumes it, failing if there is no terminator
+    fn read_until(&mut self, terminator: u8) -> Result<Vec<u8>>;
+    // the current position in the decrypted content
+    fn offset(&self) -> usize;
+    fn peek(&self) -> Option<u8>;
+    fn format_version(&self) -> u16;
 }
 pub trait EncryptedWrite {
-    fn write(&mut self, byte: u8) -> &mut Self;
     fn write_all(&mut self, buf: &[u8]) -> &mut Self;
 }
+// plaintext that is about to be sealed
+impl EncryptedWrite for Secret<Vec<u8>> {
+    fn write_all(&mut self, buf: &[u8]) -> &mut Self {
+        self.expose_mut().extend_from_slice(buf);
+        self
+    }
+}
+// decrypts a part of the content that was sealed on its own, authenticating the associated data
+// along with it
+pub trait EncryptedUnseal {
+    fn unseal(&self, sealed: &Sealed, associated_data: &[u8]) -> Result<SealedReader>;
+}
+pub trait EncryptedSeal {
+    fn seal(&self, associated_data: &[u8], content: &[u8]) -> Result<Sealed>;
+}
 pub trait EncryptedFlush{
     fn flush(self) -> Result<()>;
 }
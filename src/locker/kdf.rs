@@ -0,0 +1,110 @@
+use crate::locker::{ErrorKind, Result};
+use crate::secret::Secret;
+use crate::vec_io::VecReader;
+use argon2::{Algorithm, Argon2, Params, Version};
+use std::convert::TryInto;
+use std::time::{Duration, Instant};
+
+pub const DERIVED_KEY_SIZE: usize = 32;
+pub const KDF_PARAMS_SIZE: usize = 3 * std::mem::size_of::<u32>();
+
+// upper bounds for parameters read from a file, so that a corrupted header can't make us
+// allocate a huge amount of memory or run for hours
+const MAX_MEMORY_KIB: u32 = 4 * 1024 * 1024;
+const MAX_ITERATIONS: u32 = 1000;
+const MAX_PARALLELISM: u32 = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KdfParams {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+impl KdfParams {
+    pub const DEFAULT: KdfParams = KdfParams {
+        memory_kib: 64 * 1024,
+        iterations: 3,
+        parallelism: 1,
+    };
+    pub fn is_sane(&self) -> bool {
+        self.memory_kib <= MAX_MEMORY_KIB
+            && self.iterations <= MAX_ITERATIONS
+            && self.parallelism <= MAX_PARALLELISM
+            && Params::new(self.memory_kib, self.iterations, self.parallelism, None).is_ok()
+    }
+    pub fn derive_key(&self, password: &[u8], salt: &[u8]) -> Result<Secret<[u8; DERIVED_KEY_SIZE]>> {
+        let params = match Params::new(
+            self.memory_kib,
+            self.iterations,
+            self.parallelism,
+            Some(DERIVED_KEY_SIZE),
+        ) {
+            Ok(params) => params,
+            Err(_) => return Err(ErrorKind::KeyDerivationError.without_source_error()),
+        };
+        let mut derived_key = Secret::new([0u8; DERIVED_KEY_SIZE]);
+        match Argon2::new(Algorithm::Argon2id, Version::V0x13, params).hash_password_into(
+            password,
+            salt,
+            derived_key.expose_mut(),
+        ) {
+            Ok(()) => Ok(derived_key),
+            Err(_) => Err(ErrorKind::KeyDerivationError.without_source_error()),
+        }
+    }
+    // finds the amount of iterations that makes deriving a key with the given memory and
+    // parallelism take about the target time
+    pub fn calibrate(memory_kib: u32, parallelism: u32, target: Duration) -> Result<KdfParams> {
+        let mut params = KdfParams {
+            memory_kib,
+            iterations: 1,
+            parallelism,
+        };
+        let start = Instant::now();
+        params.derive_key(b"calibration", &[0u8; 16])?;
+        let single_iteration = start.elapsed().as_secs_f64();
+        params.iterations = ((target.as_secs_f64() / single_iteration) as u32).clamp(1, MAX_ITERATIONS);
+        Ok(params)
+    }
+    pub fn read(reader: &mut VecReader) -> Option<KdfParams> {
+        let mut buf = [0u8; KDF_PARAMS_SIZE];
+        reader.read_exact(&mut buf).ok()?;
+        let read_u32 = |index: usize| {
+            let bytes = &buf[index * 4..index * 4 + 4];
+            u32::from_le_bytes(bytes.try_into().unwrap())
+        };
+        Some(KdfParams {
+            memory_kib: read_u32(0),
+            iterations: read_u32(1),
+            parallelism: read_u32(2),
+        })
+    }
+    pub fn write_to(&self, buf: &mut [u8]) {
+        buf[..4].copy_from_slice(&self.memory_kib.to_le_bytes());
+        buf[4..8].copy_from_slice(&self.iterations.to_le_bytes());
+        buf[8..12].copy_from_slice(&self.parallelism.to_le_bytes());
+    }
+}
+
+// the key derivation options chosen by the user, where the amount of iterations is either given
+// explicitly or calibrated to take a target amount of time
+pub struct KdfOptions {
+    pub memory_kib: u32,
+    pub iterations: Option<u32>,
+    pub parallelism: u32,
+    pub target_time: Option<Duration>,
+}
+impl KdfOptions {
+    pub fn params(&self) -> Result<KdfParams> {
+        match self.target_time {
+            Some(target_time) => {
+                KdfParams::calibrate(self.memory_kib, self.parallelism, target_time)
+            }
+            None => Ok(KdfParams {
+                memory_kib: self.memory_kib,
+                iterations: self.iterations.unwrap_or(KdfParams::DEFAULT.iterations),
+                parallelism: self.parallelism,
+            }),
+        }
+    }
+}
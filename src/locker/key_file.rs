@@ -0,0 +1,67 @@
+use crate::locker::{io_to_locker_error, ErrorKind, Result, DERIVED_KEY_SIZE, PRIVATE_FILE_MODE};
+use crate::secret::Secret;
+use rand::{thread_rng, RngCore};
+use sha3::{Digest, Sha3_256};
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+
+pub const GENERATED_KEY_FILE_SIZE: usize = 32;
+const READ_CHUNK_SIZE: usize = 64 * 1024;
+
+// a file that must be given along with the password to unlock a file that requires it. any file
+// can be used as a key file, only a hash of its content is kept, so it can be arbitrarily large
+pub struct KeyFile {
+    digest: Secret<[u8; DERIVED_KEY_SIZE]>,
+}
+impl KeyFile {
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<KeyFile> {
+        let mut file = io_to_locker_error(File::open(path), ErrorKind::ReadKeyFile)?;
+        let mut hasher = Sha3_256::new();
+        let mut chunk = Secret::new(vec![0u8; READ_CHUNK_SIZE]);
+        loop {
+            let read = io_to_locker_error(file.read(chunk.expose_mut()), ErrorKind::ReadKeyFile)?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&chunk[..read]);
+        }
+        Ok(KeyFile::from_digest(hasher))
+    }
+    // writes a new key file of random bytes at the given path, which mustn't exist
+    pub fn generate<P: AsRef<Path>>(path: P) -> Result<KeyFile> {
+        let mut content = Secret::new([0u8; GENERATED_KEY_FILE_SIZE]);
+        thread_rng().fill_bytes(content.expose_mut());
+        let mut file = io_to_locker_error(
+            OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .mode(PRIVATE_FILE_MODE)
+                .open(path),
+            ErrorKind::CreateKeyFile,
+        )?;
+        io_to_locker_error(
+            file.write_all(content.expose()).and_then(|()| file.sync_all()),
+            ErrorKind::CreateKeyFile,
+        )?;
+        let mut hasher = Sha3_256::new();
+        hasher.update(content.expose());
+        Ok(KeyFile::from_digest(hasher))
+    }
+    fn from_digest(hasher: Sha3_256) -> KeyFile {
+        let mut digest = Secret::new([0u8; DERIVED_KEY_SIZE]);
+        digest.expose_mut().copy_from_slice(&hasher.finalize());
+        KeyFile { digest }
+    }
+    // combines the key derived from the password with the key file, so that the result can only be
+    // found with both of them
+    pub fn combine(&self, derived_key: &[u8]) -> Secret<[u8; DERIVED_KEY_SIZE]> {
+        let mut hasher = Sha3_256::new();
+        hasher.update(derived_key);
+        hasher.update(self.digest.expose());
+        let mut combined_key = Secret::new([0u8; DERIVED_KEY_SIZE]);
+        combined_key.expose_mut().copy_from_slice(&hasher.finalize());
+        combined_key
+    }
+}
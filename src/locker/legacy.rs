@@ -0,0 +1,239 @@
+use crate::locker::{Encryptor, DerivedKeyHeaders, ErrorKind, KdfParams, Result, SALT_LENGTH, SHA_512_DIGEST_SIZE, KDF_PARAMS_SIZE, Sha512Digest, UNVERSIONED_FORMAT_VERSION};
+use crate::secret::Secret;
+use crate::vec_io::VecReader;
+use chacha20::{ChaCha20,Nonce,cipher::NewStreamCipher,cipher::SyncStreamCipher};
+use generic_array::typenum::Unsigned;
+use sha3::{Digest, Sha3_256, Sha3_512};
+use subtle::ConstantTimeEq;
+
+// files written before the format was versioned don't have a magic, so which of the legacy layouts
+// a file uses can only be found by trying the password with each of them. they are only ever read,
+// and are rewritten in the current format the next time they are flushed.
+//
+// the oldest layouts used chacha20 with an unkeyed hash of the plaintext as their "hmac", either
+// with the password itself as the key or with a key derived from it. the last one is the current
+// layout without the magic and format version
+
+const CHACHA20_NONCE_SIZE:usize=<ChaCha20 as NewStreamCipher>::NonceSize::USIZE;
+// the headers of files that were locked before the key derivation parameters were added
+const LEGACY_PASSWORD_KEY_HEADERS_SIZE:usize=
+    SALT_LENGTH +
+    CHACHA20_NONCE_SIZE+ // nonce
+    SHA_512_DIGEST_SIZE*2;// salted key hash and hmac
+const LEGACY_DERIVED_KEY_HEADERS_SIZE:usize=LEGACY_PASSWORD_KEY_HEADERS_SIZE+KDF_PARAMS_SIZE;
+
+#[derive(Clone, Copy)]
+enum LegacyLayout {
+    PasswordKey,
+    DerivedKey,
+    Unversioned,
+}
+
+pub struct LegacyHeaders {
+    // None if the file is too short to be in one of the chacha20 layouts
+    chacha20_headers: Option<Chacha20Headers>,
+    unversioned_headers: Option<DerivedKeyHeaders>,
+}
+struct Chacha20Headers {
+    hmac:Sha512Digest,
+    nonce: Nonce,
+    salt: [u8;SALT_LENGTH],
+    salted_key_hash: Sha512Digest,
+    // None if the file is too short to contain them. in files that used the password itself as
+    // the key these bytes are actually the start of the content
+    kdf_params: Option<KdfParams>,
+}
+impl LegacyHeaders {
+    pub fn read(content:&[u8]) -> Option<LegacyHeaders> {
+        let headers=LegacyHeaders{
+            chacha20_headers:Chacha20Headers::read(content),
+            unversioned_headers:DerivedKeyHeaders::read(&mut VecReader::new(content.to_vec()),UNVERSIONED_FORMAT_VERSION).ok(),
+        };
+        if headers.chacha20_headers.is_none() && headers.unversioned_headers.is_none(){
+            return None;
+        }
+        Some(headers)
+    }
+    pub fn find_key(&self,password:&[u8])->Option<LegacyKey>{
+        // checking the password itself is cheap, so check it before deriving any key
+        if let Some(chacha20_headers)=&self.chacha20_headers{
+            if chacha20_headers.test_key_hash(password){
+                return Some(LegacyKey{key:Secret::new(password.to_vec()),layout:LegacyLayout::PasswordKey});
+            }
+        }
+        if let Some(headers)=&self.unversioned_headers{
+            if headers.kdf_params.is_sane(){
+                if let Ok(derived_key)=headers.kdf_params.derive_key(password,&headers.salt){
+                    if headers.test_derived_key(derived_key.expose()){
+                        return Some(LegacyKey{key:Secret::new(derived_key.to_vec()),layout:LegacyLayout::Unversioned});
+                    }
+                }
+            }
+        }
+        let chacha20_headers=self.chacha20_headers.as_ref()?;
+        let derived_key=chacha20_headers.derive_key(password)?;
+        Some(LegacyKey{key:derived_key,layout:LegacyLayout::DerivedKey})
+    }
+    // decrypts the content that follows the headers and validates it. if authentication isn't
+    // required, content that fails validation is returned anyway, along with whether it was valid
+    pub fn decrypt(&self,file_content:&[u8],key:&LegacyKey,require_authentication:bool)->Result<(Vec<u8>,bool)>{
+        let (content,authenticated)=match (key.layout,&self.chacha20_headers,&self.unversioned_headers){
+            (LegacyLayout::PasswordKey,Some(chacha20_headers),_)=>{
+                chacha20_headers.decrypt(&file_content[LEGACY_PASSWORD_KEY_HEADERS_SIZE..],&key.key)?
+            }
+            (LegacyLayout::DerivedKey,Some(chacha20_headers),_)=>{
+                chacha20_headers.decrypt(&file_content[LEGACY_DERIVED_KEY_HEADERS_SIZE..],&key.key)?
+            }
+            (LegacyLayout::Unversioned,_,Some(headers))=>{
+                let encryptor=Encryptor::new(&key.key);
+                let encrypted=&file_content[headers.fields_size()..];
+                match encryptor.decrypt(&headers.nonce,&headers.fields_to_bytes(),encrypted){
+                    Ok(content)=>(content,true),
+                    Err(_)=>(encryptor.decrypt_unauthenticated(&headers.nonce,encrypted)?,false),
+                }
+            }
+            _=>return Err(ErrorKind::FileNotEncryptedProperly.without_source_error()),
+        };
+        if require_authentication && !authenticated{
+            return Err(ErrorKind::MacError.without_source_error());
+        }
+        Ok((content,authenticated))
+    }
+}
+impl Chacha20Headers {
+    fn read(content:&[u8]) -> Option<Chacha20Headers> {
+        let mut reader=VecReader::new(content.to_vec());
+        let mut headers=Chacha20Headers{
+            hmac:Sha512Digest::default(),
+            nonce:Nonce::default(),
+            salt:[0u8;SALT_LENGTH],
+            salted_key_hash:Sha512Digest::default(),
+            kdf_params:None,
+        };
+        reader.read_exact(&mut headers.hmac).ok()?;
+        reader.read_exact(&mut headers.nonce).ok()?;
+        reader.read_exact(&mut headers.salt).ok()?;
+        reader.read_exact(&mut headers.salted_key_hash).ok()?;
+        headers.kdf_params=KdfParams::read(&mut reader);
+        Some(headers)
+    }
+    fn test_key_hash(&self,key:&[u8])->bool{
+        let mut hasher=Sha3_512::new();
+        hasher.update(key);
+        hasher.update(self.salt);
+        bool::from(hasher.finalize().as_slice().ct_eq(self.salted_key_hash.as_slice()))
+    }
+    fn derive_key(&self,password:&[u8])->Option<Secret<Vec<u8>>>{
+        let kdf_params = match self.kdf_params {
+            Some(kdf_params) if kdf_params.is_sane() => kdf_params,
+            _ => return None,
+        };
+        let derived_key=kdf_params.derive_key(password,&self.salt).ok()?;
+        if self.test_key_hash(derived_key.expose()){
+            Some(Secret::new(derived_key.to_vec()))
+        }else{
+            None
+        }
+    }
+    // decrypts the content, returning whether its hash matches
+    fn decrypt(&self,encrypted:&[u8],key:&[u8])->Result<(Vec<u8>,bool)>{
+        let mut content=encrypted.to_vec();
+        let mut key_hasher = Sha3_256::new();
+        key_hasher.update(key);
+        let mut chacha=ChaCha20::new(&key_hasher.finalize(),&self.nonce);
+        if chacha.try_apply_keystream(&mut content).is_err(){
+            return Err(ErrorKind::EncryptionError.without_source_error());
+        }
+        let mut hasher=Sha3_512::new();
+        hasher.update(&content);
+        let authenticated=hasher.finalize()==self.hmac;
+        Ok((content,authenticated))
+    }
+}
+
+// a key that matched one of the legacy layouts
+pub struct LegacyKey{
+    key:Secret<Vec<u8>>,
+    layout:LegacyLayout,
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::locker::{EncryptedRead, LockedEncryptedFile};
+    use crate::test_files::TempPath;
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+
+    // locked by the versions of pwm that wrote each of the layouts, with this password
+    const PASSWORD:&str="legacy password";
+    const PASSWORD_KEY_FILE:&[u8]=include_bytes!("../../testdata/legacy/password-key");
+    const DERIVED_KEY_FILE:&[u8]=include_bytes!("../../testdata/legacy/derived-key");
+    const UNVERSIONED_FILE:&[u8]=include_bytes!("../../testdata/legacy/unversioned");
+    const FILES:[(&str,&[u8]);3]=[
+        ("password-key",PASSWORD_KEY_FILE),
+        ("derived-key",DERIVED_KEY_FILE),
+        ("unversioned",UNVERSIONED_FILE),
+    ];
+
+    fn expected_content(layout:&str)->Vec<u8>{
+        format!("the content of a file locked in the {} layout\n",layout).into_bytes()
+    }
+    fn write_locked_file(content:&[u8])->TempPath{
+        let path=TempPath::unique();
+        let mut file=std::fs::OpenOptions::new().write(true).create_new(true).mode(0o600).open(&path).unwrap();
+        file.write_all(content).unwrap();
+        path
+    }
+    fn assert_content(file:&mut crate::locker::EncryptedFile,expected:&[u8]){
+        let mut reader=file.reader();
+        let mut content=vec![0u8;expected.len()];
+        reader.read_exact(&mut content).unwrap();
+        assert_eq!(content,expected);
+        assert!(reader.eof());
+    }
+
+    #[test]
+    fn each_layout_is_migrated_to_current_format(){
+        for (layout,locked) in FILES{
+            let path=write_locked_file(locked);
+            let file=LockedEncryptedFile::open_write(&path).unwrap();
+            assert!(file.is_legacy(),"{}",layout);
+            let mut unlocked=file.unlock(PASSWORD).unwrap();
+            assert_content(&mut unlocked,&expected_content(layout));
+            unlocked.rewrite_unversioned_content().unwrap();
+            drop(unlocked);
+
+            let file=LockedEncryptedFile::open_readonly(&path).unwrap();
+            assert!(!file.is_legacy(),"{}",layout);
+            let mut unlocked=file.unlock(PASSWORD).unwrap();
+            assert_content(&mut unlocked,&expected_content(layout));
+        }
+    }
+
+    #[test]
+    fn password_matches_only_the_layout_of_the_file(){
+        let layouts=[LegacyLayout::PasswordKey,LegacyLayout::DerivedKey,LegacyLayout::Unversioned];
+        for ((name,locked),layout) in FILES.iter().zip(layouts){
+            let headers=LegacyHeaders::read(locked).unwrap();
+            let key=headers.find_key(PASSWORD.as_bytes()).unwrap();
+            assert_eq!(key.layout as u8,layout as u8,"{}",name);
+            assert!(headers.find_key(b"wrong password").is_none(),"{}",name);
+        }
+    }
+
+    #[test]
+    fn tampered_content_fails_authentication(){
+        for (layout,locked) in FILES{
+            let mut tampered=locked.to_vec();
+            *tampered.last_mut().unwrap()^=1;
+            let headers=LegacyHeaders::read(&tampered).unwrap();
+            let key=headers.find_key(PASSWORD.as_bytes()).unwrap();
+            let err=headers.decrypt(&tampered,&key,true).err().unwrap();
+            assert!(matches!(err.kind(),ErrorKind::MacError),"{}",layout);
+            // the content can still be salvaged, but it's reported as unauthenticated
+            let (_,authenticated)=headers.decrypt(&tampered,&key,false).unwrap();
+            assert!(!authenticated,"{}",layout);
+        }
+    }
+}
@@ -1,12 +1,23 @@
+mod atomic;
+mod backup;
+mod cipher;
 mod errors;
+mod file_lock;
 mod flags;
 mod headers;
+mod hidden;
+mod identity;
 mod io;
 mod encrypt;
-pub use {errors::*,flags::*,headers::*,io::*,encrypt::*};
+mod kdf;
+mod key_file;
+mod legacy;
+mod permissions;
+mod shares;
+pub use {atomic::*,backup::*,cipher::*,errors::*,file_lock::*,flags::*,headers::*,hidden::*,identity::*,io::*,encrypt::*,kdf::*,key_file::*,legacy::*,permissions::*,shares::*};
 
-pub fn lock<P: AsRef<std::path::Path>>(path: P,key:&str, make_immutable: bool) -> Result<()> {
-    let mut encrypted_file=EncryptedFile::encrypt_file(path, key)?;
+pub fn lock<P: AsRef<std::path::Path>>(path: P,key:&str, kdf_params: KdfParams, cipher_suite: CipherSuite, make_immutable: bool) -> Result<()> {
+    let mut encrypted_file=EncryptedFile::encrypt_file(path, key, kdf_params, cipher_suite)?;
     if make_immutable && cfg!(unix){
         encrypted_file.inner_file_mut().make_immutable()?
     }
@@ -0,0 +1,24 @@
+use crate::locker::{io_to_locker_error, ErrorKind, Result};
+use std::fs::File;
+use std::os::unix::fs::MetadataExt;
+
+// the modes of the files and directories that pwm creates, which only their owner can access
+pub const PRIVATE_FILE_MODE: u32 = 0o600;
+pub const PRIVATE_DIR_MODE: u32 = 0o700;
+const GROUP_AND_OTHERS_PERMISSIONS: u32 = 0o077;
+const PERMISSIONS: u32 = 0o777;
+
+// fails if the file is owned by another user, or if other users can access it
+pub fn verify_private_file(file: &File) -> Result<()> {
+    let metadata = io_to_locker_error(file.metadata(), ErrorKind::ReadFile)?;
+    if metadata.uid() != unsafe { libc::geteuid() } {
+        return Err(ErrorKind::FileOwnedByAnotherUser.without_source_error());
+    }
+    if metadata.mode() & GROUP_AND_OTHERS_PERMISSIONS != 0 {
+        return Err(ErrorKind::FileAccessibleByOthers {
+            mode: metadata.mode() & PERMISSIONS,
+        }
+        .without_source_error());
+    }
+    Ok(())
+}
@@ -0,0 +1,219 @@
+use crate::locker::{ErrorKind, Result, DERIVED_KEY_SIZE};
+use crate::secret::Secret;
+use rand::{thread_rng, RngCore};
+use sha3::{Digest, Sha3_256};
+use sharks::{Share, Sharks};
+use std::convert::TryFrom;
+use std::fmt::Write;
+
+// a share is printed as hex of its version, threshold, split id and sharks share, followed by a
+// checksum of all of them, so that mistyped shares are caught before trying to recover the key
+const KEY_SHARE_VERSION: u8 = 1;
+const SPLIT_ID_SIZE: usize = 4;
+const CHECKSUM_SIZE: usize = 4;
+// the x coordinate of the share followed by a byte of each byte of the key
+const SHARKS_SHARE_SIZE: usize = 1 + DERIVED_KEY_SIZE;
+const KEY_SHARE_SIZE: usize = 2 + SPLIT_ID_SIZE + SHARKS_SHARE_SIZE + CHECKSUM_SIZE;
+// the amount of hex digits in each dash separated group of a printed share
+const HEX_GROUP_SIZE: usize = 4;
+
+// one of the shares that a key is split into, any threshold of which recover the key
+pub struct KeyShare {
+    pub threshold: u8,
+    // random for each split, so that shares of different splits can't be mixed up
+    pub split_id: [u8; SPLIT_ID_SIZE],
+    share: Secret<Vec<u8>>,
+}
+impl KeyShare {
+    fn checksum(payload: &[u8]) -> [u8; CHECKSUM_SIZE] {
+        let mut checksum = [0u8; CHECKSUM_SIZE];
+        checksum.copy_from_slice(&Sha3_256::digest(payload)[..CHECKSUM_SIZE]);
+        checksum
+    }
+    fn payload(&self) -> Secret<Vec<u8>> {
+        let mut payload = Secret::new(Vec::with_capacity(KEY_SHARE_SIZE));
+        payload.expose_mut().push(KEY_SHARE_VERSION);
+        payload.expose_mut().push(self.threshold);
+        payload.expose_mut().extend_from_slice(&self.split_id);
+        payload.expose_mut().extend_from_slice(&self.share);
+        payload
+    }
+    pub fn to_hex(&self) -> Secret<String> {
+        let mut bytes = self.payload();
+        let checksum = KeyShare::checksum(&bytes);
+        bytes.expose_mut().extend_from_slice(&checksum);
+        let mut hex = Secret::new(String::with_capacity(KEY_SHARE_SIZE * 3));
+        for (index, byte) in bytes.iter().enumerate() {
+            if index > 0 && index * 2 % HEX_GROUP_SIZE == 0 {
+                hex.expose_mut().push('-');
+            }
+            // writing to a string can't fail
+            let _ = write!(hex.expose_mut(), "{:02x}", byte);
+        }
+        hex
+    }
+    // whitespace and dashes are ignored, so the share can be typed in any grouping
+    pub fn from_hex(hex: &str) -> Result<KeyShare> {
+        let invalid_share = || ErrorKind::InvalidKeyShare.without_source_error();
+        let digits = Secret::new(
+            hex.chars()
+                .filter(|c| !c.is_whitespace() && *c != '-')
+                .map(|c| c.to_digit(16).map(|digit| digit as u8))
+                .collect::<Option<Vec<u8>>>()
+                .ok_or_else(invalid_share)?,
+        );
+        if digits.len() != KEY_SHARE_SIZE * 2 {
+            return Err(invalid_share());
+        }
+        let bytes = Secret::new(
+            digits
+                .chunks(2)
+                .map(|pair| pair[0] << 4 | pair[1])
+                .collect::<Vec<u8>>(),
+        );
+        let (payload, checksum) = bytes.split_at(KEY_SHARE_SIZE - CHECKSUM_SIZE);
+        if KeyShare::checksum(payload) != checksum || payload[0] != KEY_SHARE_VERSION {
+            return Err(invalid_share());
+        }
+        let mut split_id = [0u8; SPLIT_ID_SIZE];
+        split_id.copy_from_slice(&payload[2..2 + SPLIT_ID_SIZE]);
+        Ok(KeyShare {
+            threshold: payload[1],
+            split_id,
+            share: Secret::new(payload[2 + SPLIT_ID_SIZE..].to_vec()),
+        })
+    }
+}
+
+// splits the key into the given amount of shares, any threshold of which recover it
+pub fn split_key(key: &[u8], shares: u8, threshold: u8) -> Result<Vec<KeyShare>> {
+    if threshold == 0 || threshold > shares {
+        return Err(ErrorKind::InvalidShareThreshold.without_source_error());
+    }
+    let mut split_id = [0u8; SPLIT_ID_SIZE];
+    thread_rng().fill_bytes(&mut split_id);
+    Ok(Sharks(threshold)
+        .dealer(key)
+        .take(shares as usize)
+        .map(|share| KeyShare {
+            threshold,
+            split_id,
+            share: Secret::new(Vec::from(&share)),
+        })
+        .collect())
+}
+
+// the shares must all be of the same split, and there must be at least as many of them as the
+// threshold
+pub fn recover_key(shares: &[KeyShare]) -> Result<Secret<[u8; DERIVED_KEY_SIZE]>> {
+    let first_share = match shares.first() {
+        Some(first_share) => first_share,
+        None => return Err(ErrorKind::NotEnoughKeyShares.without_source_error()),
+    };
+    if shares.iter().any(|share| {
+        share.split_id != first_share.split_id || share.threshold != first_share.threshold
+    }) {
+        return Err(ErrorKind::KeySharesOfDifferentSplits.without_source_error());
+    }
+    let sharks_shares = shares
+        .iter()
+        .map(|share| Share::try_from(share.share.as_slice()))
+        .collect::<std::result::Result<Vec<Share>, _>>()
+        .map_err(|_| ErrorKind::InvalidKeyShare.without_source_error())?;
+    let recovered = match Sharks(first_share.threshold).recover(&sharks_shares) {
+        Ok(recovered) => Secret::new(recovered),
+        Err(_) => return Err(ErrorKind::NotEnoughKeyShares.without_source_error()),
+    };
+    let mut key = Secret::new([0u8; DERIVED_KEY_SIZE]);
+    key.expose_mut().copy_from_slice(&recovered);
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: [u8; DERIVED_KEY_SIZE] = [7; DERIVED_KEY_SIZE];
+
+    // the shares with the given indices, parsed back from their printed form like typed shares
+    fn typed_shares(shares: &[KeyShare], indices: &[usize]) -> Vec<KeyShare> {
+        indices
+            .iter()
+            .map(|index| KeyShare::from_hex(&shares[*index].to_hex()).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn threshold_of_shares_recovers_key() {
+        let shares = split_key(&KEY, 5, 3).unwrap();
+        for indices in [[0, 1, 2], [2, 3, 4], [4, 0, 2]] {
+            let recovered = recover_key(&typed_shares(&shares, &indices)).unwrap();
+            assert_eq!(*recovered.expose(), KEY);
+        }
+    }
+
+    #[test]
+    fn fewer_shares_than_threshold_are_rejected() {
+        let shares = split_key(&KEY, 5, 3).unwrap();
+        for indices in [&[][..], &[0], &[1, 4]] {
+            let err = recover_key(&typed_shares(&shares, indices)).err().unwrap();
+            assert!(matches!(err.kind(), ErrorKind::NotEnoughKeyShares));
+        }
+    }
+
+    #[test]
+    fn repeated_share_doesnt_count_twice() {
+        let shares = split_key(&KEY, 5, 3).unwrap();
+        let err = recover_key(&typed_shares(&shares, &[0, 1, 1]))
+            .err()
+            .unwrap();
+        assert!(matches!(err.kind(), ErrorKind::NotEnoughKeyShares));
+    }
+
+    #[test]
+    fn shares_of_different_splits_are_rejected() {
+        let first_split = split_key(&KEY, 3, 2).unwrap();
+        let second_split = split_key(&KEY, 3, 2).unwrap();
+        let mut shares = typed_shares(&first_split, &[0]);
+        shares.extend(typed_shares(&second_split, &[1]));
+        let err = recover_key(&shares).err().unwrap();
+        assert!(matches!(err.kind(), ErrorKind::KeySharesOfDifferentSplits));
+    }
+
+    #[test]
+    fn mistyped_share_fails_checksum() {
+        let shares = split_key(&KEY, 3, 2).unwrap();
+        let hex = shares[0].to_hex();
+        // change a single digit of every group of the share, including the checksum itself
+        for index in (0..hex.len()).step_by(HEX_GROUP_SIZE + 1) {
+            let mut mistyped = hex.expose().clone();
+            let digit = if &hex[index..=index] == "0" { "1" } else { "0" };
+            mistyped.replace_range(index..=index, digit);
+            let err = KeyShare::from_hex(&mistyped).err().unwrap();
+            assert!(matches!(err.kind(), ErrorKind::InvalidKeyShare));
+        }
+    }
+
+    #[test]
+    fn share_can_be_typed_in_any_grouping() {
+        let shares = split_key(&KEY, 3, 2).unwrap();
+        let ungrouped = shares[0].to_hex().replace('-', "");
+        let regrouped = ungrouped
+            .as_bytes()
+            .chunks(8)
+            .map(|chunk| std::str::from_utf8(chunk).unwrap())
+            .collect::<Vec<&str>>()
+            .join(" ");
+        let mut typed = vec![KeyShare::from_hex(&regrouped).unwrap()];
+        typed.extend(typed_shares(&shares, &[2]));
+        assert_eq!(*recover_key(&typed).unwrap().expose(), KEY);
+    }
+
+    #[test]
+    fn threshold_larger_than_shares_is_rejected() {
+        for (shares, threshold) in [(3, 4), (3, 0)] {
+            let err = split_key(&KEY, shares, threshold).err().unwrap();
+            assert!(matches!(err.kind(), ErrorKind::InvalidShareThreshold));
+        }
+    }
+}
@@ -1,25 +1,163 @@
+mod cli;
+mod config;
+mod editor;
+mod hardening;
 mod wrapped_clipboard;
 mod commands;
 mod input;
 mod locker;
 mod passwords;
+mod secret;
 mod styles;
 mod vec_io;
-use commands::pwm::printing::PrintingMode;
-use passwords::{PasswordFilter, PasswordGeneratorOptions, SortBy};
-use std::collections::HashMap;
-fn main() {
-    let filter = PasswordFilter::new(None, Some("git".to_string()), None, HashMap::new());
-    commands::pwm::get_command(
-        filter,
-        Some(SortBy::Other("Phone Number".to_string())),
-        PrintingMode::Verbose,
-    );
-    // commands::pwm::new_command(
-    //     None,
-    //     "roee_.s".to_string(),
-    //     "instagram.com".to_string(),
-    //     HashMap::new(),
-    //     PasswordGeneratorOptions::new(),
-    // );
+#[cfg(test)]
+mod test_files;
+use clap::Parser;
+use commands::NewKeySlot;
+use cli::{
+    fields_map, printing_mode, BackupCommand, Cli, Command, DecoyCommand, IdentityCommand, KeyfileCommand,
+    RecipientsCommand, SlotCommand, TrashCommand,
+};
+use std::process::ExitCode;
+fn main() -> ExitCode {
+    match Cli::parse().command {
+        Command::Lock {
+            path,
+            immutable,
+            cipher,
+            kdf,
+        } => commands::lock_command(&path, kdf.options(), cipher, immutable),
+        Command::Unlock { path, keyfile } => commands::unlock_command(&path, keyfile.as_deref()),
+        Command::Upgrade { path, entries } => commands::upgrade_command(path, entries),
+        Command::Check { path, entries } => commands::check_command(path, entries),
+        Command::Get {
+            filter,
+            sort_by,
+            verbose,
+            keyfile,
+        } => commands::pwm::get_command(
+            filter.into(),
+            sort_by,
+            printing_mode(verbose),
+            keyfile.as_deref(),
+        ),
+        Command::New {
+            username,
+            domain,
+            fields,
+            password,
+            generator,
+            keyfile,
+        } => commands::pwm::new_command(
+            password,
+            username,
+            domain,
+            fields_map(fields),
+            generator.into(),
+            keyfile.as_deref(),
+        ),
+        Command::Edit { filter, changes } => {
+            commands::pwm::edit_command(filter.into(), changes.into())
+        }
+        Command::Passwd { kdf } => commands::pwm::passwd_command(kdf.options()),
+        Command::Calibrate { target_time, kdf } => {
+            commands::calibrate_command(kdf.options(None, Some(target_time)))
+        }
+        Command::Remove { filter, yes } => commands::pwm::remove_command(filter.into(), yes),
+        Command::Trash { command } => match command {
+            TrashCommand::List => commands::pwm::trash_list_command(),
+            TrashCommand::Restore { filter, yes } => {
+                commands::pwm::trash_restore_command(filter.into(), yes)
+            }
+            TrashCommand::Purge { filter, yes } => {
+                commands::pwm::trash_purge_command(filter.into(), yes)
+            }
+        },
+        Command::Salvage {
+            output,
+            from,
+            ignore_authentication,
+        } => commands::pwm::salvage_command(from, &output, ignore_authentication),
+        Command::Split { shares, threshold } => commands::pwm::split_command(shares, threshold),
+        Command::Recover { kdf } => commands::pwm::recover_command(kdf.options()),
+        Command::Slot { command } => match command {
+            SlotCommand::List { path } => commands::slot_list_command(path.as_deref()),
+            SlotCommand::Add {
+                path,
+                label,
+                recovery,
+                slot_keyfile,
+                keyfile,
+                kdf,
+            } => commands::slot_add_command(
+                path.as_deref(),
+                keyfile.as_deref(),
+                NewKeySlot {
+                    label,
+                    recovery,
+                    key_file: slot_keyfile.as_deref(),
+                    kdf_options: kdf.options(),
+                },
+            ),
+            SlotCommand::Revoke {
+                id,
+                path,
+                keyfile,
+                yes,
+            } => commands::slot_revoke_command(id, path.as_deref(), keyfile.as_deref(), yes),
+        },
+        Command::Keyfile { command } => match command {
+            KeyfileCommand::Add {
+                keyfile,
+                path,
+                generate,
+            } => commands::keyfile_add_command(&keyfile, path.as_deref(), generate),
+            KeyfileCommand::Remove { path, keyfile } => {
+                commands::keyfile_remove_command(path.as_deref(), keyfile.as_deref())
+            }
+        },
+        Command::Decoy { command } => match command {
+            DecoyCommand::Create { yes } => commands::pwm::decoy_create_command(yes),
+            DecoyCommand::Remove { yes } => commands::pwm::decoy_remove_command(yes),
+        },
+        Command::Identity { command } => match command {
+            IdentityCommand::New => commands::identity_new_command(),
+            IdentityCommand::Show => commands::identity_show_command(),
+        },
+        Command::Recipients { command } => match command {
+            RecipientsCommand::Add {
+                public_key,
+                path,
+                label,
+                keyfile,
+            } => commands::recipients_add_command(
+                &public_key,
+                path.as_deref(),
+                label,
+                keyfile.as_deref(),
+            ),
+            RecipientsCommand::Remove {
+                public_key,
+                path,
+                keyfile,
+                yes,
+            } => commands::recipients_remove_command(
+                &public_key,
+                path.as_deref(),
+                keyfile.as_deref(),
+                yes,
+            ),
+        },
+        Command::Share {
+            public_key,
+            output,
+            filter,
+        } => commands::pwm::share_command(&public_key, &output, filter.into()),
+        Command::Receive { path } => commands::pwm::receive_command(&path),
+        Command::Backup { command } => match command {
+            BackupCommand::List => commands::pwm::backup_list_command(),
+            BackupCommand::Restore { id, yes } => commands::pwm::backup_restore_command(id, yes),
+        },
+        Command::Completions { shell } => commands::completions_command(shell),
+    }
 }
@@ -1,5 +1,5 @@
 use crate::locker::{Error, Result};
-use crate::passwords::{iterator::PasswordIterator, Password};
+use crate::passwords::{iterator::PasswordIterator, Password, PasswordEntry};
 use fallible_iterator::FallibleIterator;
 use std::collections::HashMap;
 
@@ -23,22 +23,33 @@ impl PasswordFilter {
             additional_filters,
         }
     }
-    pub fn test(&self, password: &Password) -> bool {
-        if let Some(pf) = &self.password_filter {
-            if !password.password.contains(&pf[..]) {
+    // tests only the domain and username, which can be tested before the entry is opened, so
+    // that entries that can't match aren't decrypted
+    pub fn test_entry(&self, entry: &PasswordEntry) -> bool {
+        self.test_domain_and_username(&entry.domain, &entry.username)
+    }
+    fn test_domain_and_username(&self, domain: &str, username: &str) -> bool {
+        if let Some(df) = &self.domain_filter {
+            if !domain.contains(&df[..]) {
                 return false;
             }
         }
-        if let Some(df) = &self.domain_filter {
-            if !password.domain.contains(&df[..]) {
+        if let Some(uf) = &self.username_filter {
+            if !username.contains(&uf[..]) {
                 return false;
             }
         }
-        if let Some(uf) = &self.username_filter {
-            if !password.username.contains(&uf[..]) {
+        true
+    }
+    pub fn test(&self, password: &Password) -> bool {
+        if let Some(pf) = &self.password_filter {
+            if !password.password.contains(&pf[..]) {
                 return false;
             }
         }
+        if !self.test_domain_and_username(&password.domain, &password.username) {
+            return false;
+        }
         for (filter_key, filter) in &self.additional_filters {
             match password.additional_fields.get(filter_key) {
                 Some(value) => {
@@ -76,13 +87,16 @@ impl<'a> FallibleIterator for FilteredPasswordIterator<'a> {
     type Error = Error;
     fn next(&mut self) -> Result<Option<Password>> {
         loop {
-            match self.iterator.next()? {
-                Some(p) => {
-                    if self.filter.test(&p) {
-                        return Ok(Some(p));
-                    }
-                }
+            let entry = match self.iterator.next_entry()? {
+                Some(entry) => entry,
                 None => return Ok(None),
+            };
+            if !self.filter.test_entry(&entry) {
+                continue;
+            }
+            let password = self.iterator.open(entry)?;
+            if self.filter.test(&password) {
+                return Ok(Some(password));
             }
         }
     }
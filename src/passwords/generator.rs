@@ -1,6 +1,7 @@
-use rand::{thread_rng, Rng, RngCore};
+use rand::{thread_rng, Rng};
 
 use crate::locker::{ErrorKind, Result};
+use crate::secret::Secret;
 
 pub const DEFAULT_PASSWORD_LENGTH: usize = 20;
 
@@ -43,7 +44,7 @@ impl PasswordGeneratorOptions {
     }
 }
 
-pub fn generate_password(options: &PasswordGeneratorOptions) -> Result<String> {
+pub fn generate_password(options: &PasswordGeneratorOptions) -> Result<Secret<String>> {
     if options.password_length == 0 {
         return Err(ErrorKind::PasswordLengthZero.without_source_error());
     }
@@ -54,5 +55,5 @@ pub fn generate_password(options: &PasswordGeneratorOptions) -> Result<String> {
     for _ in 0..options.password_length{
         result.push(dict[random.gen_range::<usize, _, _>(0, dict_length)])
     }
-    Ok(result)
+    Ok(Secret::new(result))
 }
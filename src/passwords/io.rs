@@ -1,41 +1,86 @@
 use std::collections::HashMap;
 
 use crate::locker::{
-    to_locker_error, EncryptedFile, ErrorKind, Result, EncryptedWrite, ENCRYPTION_HEADERS_SIZE,
+    to_locker_error, EncryptedSeal, EncryptedUnseal, EncryptedWrite, ErrorKind, Result, Sealed,
 };
 
 use crate::passwords::Password;
+use crate::secret::Secret;
+
+// the format version from which integers are written as little endian u64s, before it they were
+// native endian usizes, which could only be read on the machine that wrote them
+const PORTABLE_INTEGERS_FORMAT_VERSION: u16 = 2;
+// the format version from which strings are prefixed with their length, before it they were
+// terminated with a nul, so they couldn't contain one
+const LENGTH_PREFIXED_STRINGS_FORMAT_VERSION: u16 = 3;
+// the format version from which the password and additional fields of each entry are sealed on
+// their own, so that only the entries that are needed are decrypted. the domain and username are
+// left in the content as an index of the entries
+const SEALED_ENTRIES_FORMAT_VERSION: u16 = 7;
+const SEALED_ENTRY_ASSOCIATED_DATA: &[u8] = b"pwm entry";
+
+// an entry whose domain and username can be read, but whose password and additional fields might
+// still be sealed
+pub struct PasswordEntry {
+    pub domain: String,
+    pub username: String,
+    fields: EntryFields,
+}
+enum EntryFields {
+    // entries written before entries were sealed
+    Plain {
+        password: Secret<String>,
+        additional_fields: HashMap<String, String>,
+    },
+    Sealed(Sealed),
+}
+
+// the domain and username are authenticated along with the sealed fields, so that the index can't
+// be swapped between entries
+fn sealed_entry_associated_data(domain: &str, username: &str) -> Vec<u8> {
+    let mut associated_data = SEALED_ENTRY_ASSOCIATED_DATA.to_vec();
+    for field in &[domain, username] {
+        associated_data.extend_from_slice(&(field.len() as u64).to_le_bytes());
+        associated_data.extend_from_slice(field.as_bytes());
+    }
+    associated_data
+}
+
 pub trait PasswordReader {
-    fn read_usize(&mut self) -> Result<usize>;
+    fn read_u64(&mut self) -> Result<u64>;
     fn read_string(&mut self) -> Result<String>;
-    fn read_optional_string(&mut self) -> Result<Option<String>>;
     fn read_additional_fields(&mut self) -> Result<HashMap<String, String>>;
-    fn read_password(&mut self) -> Result<Password>;
+    // reads an entry without decrypting its sealed fields
+    fn read_password_entry(&mut self) -> Result<PasswordEntry>;
+    fn open_password_entry(&self, entry: PasswordEntry) -> Result<Password>
+    where
+        Self: EncryptedUnseal;
+    fn read_password(&mut self) -> Result<Password>
+    where
+        Self: EncryptedUnseal;
 }
 impl<T: crate::locker::EncryptedRead> PasswordReader for T {
-    fn read_usize(&mut self) -> Result<usize> {
-        let mut usize_buf = [0u8; std::mem::size_of::<usize>()];
-        self.read_exact(&mut usize_buf)?;
-        Ok(usize::from_ne_bytes(usize_buf))
+    fn read_u64(&mut self) -> Result<u64> {
+        if self.format_version() < PORTABLE_INTEGERS_FORMAT_VERSION {
+            let mut usize_buf = [0u8; std::mem::size_of::<usize>()];
+            self.read_exact(&mut usize_buf)?;
+            return Ok(usize::from_ne_bytes(usize_buf) as u64);
+        }
+        let mut u64_buf = [0u8; std::mem::size_of::<u64>()];
+        self.read_exact(&mut u64_buf)?;
+        Ok(u64::from_le_bytes(u64_buf))
     }
     fn read_string(&mut self) -> Result<String> {
-        to_locker_error(
-            String::from_utf8(self.read_until(0)),
-            ErrorKind::EncodingError,
-        )
-    }
-    fn read_optional_string(&mut self) -> Result<Option<String>> {
-        let string = to_locker_error(
-            String::from_utf8(self.read_until(0)),
-            ErrorKind::EncodingError,
-        )?;
-        Ok(match string.len() {
-            0 => None,
-            _ => Some(string),
-        })
+        let bytes = if self.format_version() < LENGTH_PREFIXED_STRINGS_FORMAT_VERSION {
+            self.read_until(0)?
+        } else {
+            let length = self.read_u64()?;
+            self.read_bytes(length)?
+        };
+        to_locker_error(String::from_utf8(bytes), ErrorKind::EncodingError)
     }
     fn read_additional_fields(&mut self) -> Result<HashMap<String, String>> {
-        let amount = self.read_usize()?;
+        let amount = self.read_u64()?;
         let mut fields = HashMap::new();
         for _ in 0..amount {
             let key = self.read_string()?;
@@ -44,51 +89,109 @@ impl<T: crate::locker::EncryptedRead> PasswordReader for T {
         }
         Ok(fields)
     }
-    fn read_password(&mut self) -> Result<Password> {
-        Ok(Password {
-            password: self.read_string()?,
+    fn read_password_entry(&mut self) -> Result<PasswordEntry> {
+        if self.format_version() < SEALED_ENTRIES_FORMAT_VERSION {
+            let password = Secret::new(self.read_string()?);
+            return Ok(PasswordEntry {
+                domain: self.read_string()?,
+                username: self.read_string()?,
+                fields: EntryFields::Plain {
+                    password,
+                    additional_fields: self.read_additional_fields()?,
+                },
+            });
+        }
+        Ok(PasswordEntry {
             domain: self.read_string()?,
             username: self.read_string()?,
-            additional_fields: self.read_additional_fields()?,
+            fields: EntryFields::Sealed(Sealed::read(self)?),
         })
     }
+    fn open_password_entry(&self, entry: PasswordEntry) -> Result<Password>
+    where
+        Self: EncryptedUnseal,
+    {
+        let (password, additional_fields) = match entry.fields {
+            EntryFields::Plain {
+                password,
+                additional_fields,
+            } => (password, additional_fields),
+            EntryFields::Sealed(sealed) => {
+                let associated_data = sealed_entry_associated_data(&entry.domain, &entry.username);
+                let mut reader = self.unseal(&sealed, &associated_data)?;
+                (
+                    Secret::new(reader.read_string()?),
+                    reader.read_additional_fields()?,
+                )
+            }
+        };
+        Ok(Password {
+            password,
+            domain: entry.domain,
+            username: entry.username,
+            additional_fields,
+        })
+    }
+    fn read_password(&mut self) -> Result<Password>
+    where
+        Self: EncryptedUnseal,
+    {
+        let entry = self.read_password_entry()?;
+        self.open_password_entry(entry)
+    }
 }
 
 pub trait PasswordWriter {
-    fn write_usize(&mut self, usize_value: usize) -> &mut Self;
+    fn write_u64(&mut self, value: u64) -> &mut Self;
     fn write_string(&mut self, string: &str) -> &mut Self;
     fn write_additional_fields(&mut self, additional_fields: &HashMap<String, String>)
         -> &mut Self;
-    fn write_password(&mut self, password: &Password) -> &mut Self;
-    fn write_passwords(&mut self, passwords: &[Password]) -> &mut Self;
+    fn write_password(&mut self, password: &Password) -> Result<&mut Self>
+    where
+        Self: EncryptedSeal;
+    fn write_passwords(&mut self, passwords: &[Password]) -> Result<&mut Self>
+    where
+        Self: EncryptedSeal;
 }
 impl<T: EncryptedWrite> PasswordWriter for T {
-    fn write_usize(&mut self, usize_value: usize) -> &mut Self {
-        self.write_all(&usize_value.to_ne_bytes())
+    fn write_u64(&mut self, value: u64) -> &mut Self {
+        self.write_all(&value.to_le_bytes())
     }
     fn write_string(&mut self, string: &str) -> &mut Self {
-        self.write_all(string.as_bytes()).write(0)
+        self.write_u64(string.len() as u64)
+            .write_all(string.as_bytes())
     }
     fn write_additional_fields(
         &mut self,
         additional_fields: &HashMap<String, String>,
     ) -> &mut Self {
-        self.write_usize(additional_fields.len());
+        self.write_u64(additional_fields.len() as u64);
         for (key, value) in additional_fields {
             self.write_string(key).write_string(value);
         }
         self
     }
-    fn write_password(&mut self, password: &Password) -> &mut Self {
-        self.write_string(&password.password)
-            .write_string(&password.domain)
-            .write_string(&password.username)
-            .write_additional_fields(&password.additional_fields)
+    fn write_password(&mut self, password: &Password) -> Result<&mut Self>
+    where
+        Self: EncryptedSeal,
+    {
+        let mut fields = Secret::new(Vec::new());
+        fields
+            .write_string(&password.password)
+            .write_additional_fields(&password.additional_fields);
+        let associated_data = sealed_entry_associated_data(&password.domain, &password.username);
+        let sealed = self.seal(&associated_data, &fields)?;
+        self.write_string(&password.domain)
+            .write_string(&password.username);
+        Ok(sealed.write_to(self))
     }
-    fn write_passwords(&mut self, passwords: &[Password]) -> &mut Self {
+    fn write_passwords(&mut self, passwords: &[Password]) -> Result<&mut Self>
+    where
+        Self: EncryptedSeal,
+    {
         for password in passwords {
-            self.write_password(password);
+            self.write_password(password)?;
         }
-        self
+        Ok(self)
     }
 }
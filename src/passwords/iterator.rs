@@ -1,34 +1,67 @@
-use crate::locker::{Error, Result,EncryptedFile, EncryptedFileReader, ErrorKind};
+use crate::locker::{Error, Result,EncryptedFile, EncryptedFileReader, EncryptedRead, ErrorKind};
 use crate::passwords::io::PasswordReader;
-use crate::passwords::Password;
+use crate::passwords::trash::TrashReader;
+use crate::passwords::{Password, PasswordEntry};
 use fallible_iterator::FallibleIterator;
 pub struct PasswordIterator<'a> {
     reader: EncryptedFileReader<'a>,
+    skipped_trash_section: bool,
 }
 impl<'a> PasswordIterator<'a> {
-    pub fn new(file: &'a mut EncryptedFile) -> PasswordIterator {
+    pub fn new(file: &'a mut EncryptedFile) -> PasswordIterator<'a> {
         PasswordIterator {
             reader: file.reader(),
+            skipped_trash_section: false,
         }
     }
+    // the offset in the decrypted content of the next entry
+    pub fn offset(&self) -> usize {
+        self.reader.offset()
+    }
+    // called by the first call to next if it wasn't called before, calling it separately allows
+    // telling errors in the trash section apart from errors in the first entry
+    pub fn skip_trash_section(&mut self) -> Result<()> {
+        if self.skipped_trash_section {
+            return Ok(());
+        }
+        if let Err(e) = self.reader.skip_trash_section() {
+            return Err(self.corrupted_file_error(e));
+        }
+        self.skipped_trash_section = true;
+        Ok(())
+    }
+    fn corrupted_file_error(&self, e: Error) -> Error {
+        match e.kind() {
+            ErrorKind::ReadFile => e.with_kind(ErrorKind::CorruptedFile {
+                offset: self.reader.offset(),
+            }),
+            _ => e,
+        }
+    }
+    // reads the next entry without decrypting its sealed fields, so that entries can be skipped by
+    // their domain and username without decrypting them
+    pub fn next_entry(&mut self) -> Result<Option<PasswordEntry>> {
+        self.skip_trash_section()?;
+        if self.reader.eof() {
+            return Ok(None);
+        }
+        match self.reader.read_password_entry() {
+            Ok(entry) => Ok(Some(entry)),
+            Err(e) => Err(self.corrupted_file_error(e)),
+        }
+    }
+    pub fn open(&self, entry: PasswordEntry) -> Result<Password> {
+        self.reader.open_password_entry(entry)
+    }
 }
 
 impl<'a> FallibleIterator for PasswordIterator<'a> {
     type Error = Error;
     type Item = Password;
     fn next(&mut self) -> Result<Option<Self::Item>> {
-        Ok(if self.reader.eof() {
-            None
-        } else {
-            Some(match self.reader.read_password() {
-                Ok(v) => v,
-                Err(e) => {
-                    return Err(match e.kind() {
-                        ErrorKind::ReadFile => e.with_kind(ErrorKind::CorruptedFile),
-                        _ => e,
-                    })
-                }
-            })
-        })
+        match self.next_entry()? {
+            Some(entry) => self.open(entry).map(Some),
+            None => Ok(None),
+        }
     }
 }
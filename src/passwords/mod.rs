@@ -3,13 +3,17 @@ mod iterator;
 mod filter;
 mod sort;
 mod generator;
-pub use {io::*,iterator::*,sort::*,filter::*,generator::*};
+mod trash;
+mod salvage;
+mod share;
+pub use {io::*,iterator::*,sort::*,filter::*,generator::*,trash::*,salvage::*,share::*};
 
+use crate::secret::Secret;
 use std::collections::HashMap;
 
 #[derive(Debug)]
 pub struct Password{
-    pub password:String,
+    pub password:Secret<String>,
     pub domain:String,
     pub username:String,
     pub additional_fields:HashMap<String,String>,
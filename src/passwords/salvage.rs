@@ -0,0 +1,303 @@
+use crate::locker::{EncryptedFile, EncryptedFileReader, EncryptedRead};
+use crate::passwords::{
+    Password, PasswordReader, PasswordsFileContent, TrashReader, TrashedPassword,
+};
+use std::ops::Range;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// entries have no markers, so after a corrupted entry the next one is found by trying to read an
+// entry at each of the following offsets. a trashed password starts with the time it was removed
+// at, while a password starts with the length of a string, so while resynchronising a trashed
+// password is only accepted if it was removed at a plausible time
+const MIN_PLAUSIBLE_REMOVAL_TIME: u64 = 946_684_800; // 2000-01-01
+const MAX_CLOCK_SKEW_SECONDS: u64 = 24 * 60 * 60;
+
+fn is_plausible_removal_time(removed_at: u64) -> bool {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(u64::MAX - MAX_CLOCK_SKEW_SECONDS);
+    (MIN_PLAUSIBLE_REMOVAL_TIME..=now + MAX_CLOCK_SKEW_SECONDS).contains(&removed_at)
+}
+
+enum Entry {
+    Trashed(TrashedPassword),
+    Password(Password),
+}
+
+// the entries that could be read from the passwords file
+pub struct SalvagedContent {
+    pub content: PasswordsFileContent,
+    // the ranges of the decrypted content that were skipped since no entry could be read from them
+    pub skipped: Vec<Range<usize>>,
+}
+
+// reads an entry at the current offset, and moves past it if it was read. the trash section is
+// stored before the passwords, so trashed passwords are only expected while some remain
+fn read_entry(
+    reader: &mut EncryptedFileReader,
+    trashed_remaining: u64,
+    resynchronising: bool,
+) -> Option<Entry> {
+    let start = reader.offset();
+    if trashed_remaining > 0 {
+        match reader.read_trashed_password() {
+            Ok(trashed)
+                if !resynchronising || is_plausible_removal_time(trashed.removed_at) =>
+            {
+                return Some(Entry::Trashed(trashed))
+            }
+            _ => reader.seek(start),
+        }
+    }
+    match reader.read_password() {
+        Ok(password) => Some(Entry::Password(password)),
+        Err(_) => {
+            reader.seek(start);
+            None
+        }
+    }
+}
+
+fn trashed_remaining_after(entry: &Entry, trashed_remaining: u64) -> u64 {
+    match entry {
+        Entry::Trashed(_) => trashed_remaining.saturating_sub(1),
+        Entry::Password(_) => 0,
+    }
+}
+
+// returns the first offset from the given one at which an entry can be read, that is followed by
+// either another entry or the end of the content, which makes it unlikely that the entry was read
+// from the middle of another one. returns the end of the content if there is no such offset
+fn resynchronise(reader: &mut EncryptedFileReader, from: usize, trashed_remaining: u64) -> usize {
+    let mut offset = from;
+    loop {
+        reader.seek(offset);
+        if reader.eof() {
+            return reader.offset();
+        }
+        if let Some(entry) = read_entry(reader, trashed_remaining, true) {
+            let trashed_remaining = trashed_remaining_after(&entry, trashed_remaining);
+            if reader.eof() || read_entry(reader, trashed_remaining, true).is_some() {
+                reader.seek(offset);
+                return offset;
+            }
+        }
+        offset += 1;
+    }
+}
+
+// reads every entry that can be read from the content of the passwords file, skipping over the
+// corrupted parts of it instead of failing
+pub fn salvage_passwords_file(file: &mut EncryptedFile) -> SalvagedContent {
+    let mut reader = file.reader();
+    let mut salvaged = SalvagedContent {
+        content: PasswordsFileContent {
+            passwords: Vec::new(),
+            trash: Vec::new(),
+        },
+        skipped: Vec::new(),
+    };
+    // if the amount of trashed passwords can't be read, any of the entries might be trashed
+    let mut trashed_remaining = reader.read_trash_section_header().unwrap_or(u64::MAX);
+    while !reader.eof() {
+        let start = reader.offset();
+        let entry = match read_entry(&mut reader, trashed_remaining, false) {
+            Some(entry) => entry,
+            None => {
+                let end = resynchronise(&mut reader, start + 1, trashed_remaining);
+                salvaged.skipped.push(start..end);
+                continue;
+            }
+        };
+        trashed_remaining = trashed_remaining_after(&entry, trashed_remaining);
+        match entry {
+            Entry::Trashed(trashed) => salvaged.content.trash.push(trashed),
+            Entry::Password(password) => salvaged.content.passwords.push(password),
+        }
+    }
+    salvaged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::locker::{CipherSuite, EncryptedFlush, EncryptedWrite, KdfParams};
+    use crate::secret::Secret;
+    use crate::test_files::TempPath;
+    use std::collections::HashMap;
+
+    const KDF_PARAMS: KdfParams = KdfParams {
+        memory_kib: 8,
+        iterations: 1,
+        parallelism: 1,
+    };
+
+    fn password(domain: &str) -> Password {
+        Password {
+            password: Secret::new(format!("password of {}", domain)),
+            domain: domain.to_string(),
+            username: "user".to_string(),
+            additional_fields: HashMap::new(),
+        }
+    }
+
+    // a passwords file with two trashed passwords followed by three passwords, along with the
+    // offset at which each entry starts, followed by the end of the content
+    fn passwords_file(path: &TempPath) -> (EncryptedFile, Vec<usize>) {
+        let mut file =
+            EncryptedFile::create(path, "password", KDF_PARAMS, CipherSuite::DEFAULT).unwrap();
+        PasswordsFileContent {
+            passwords: vec![password("a.com"), password("b.com"), password("c.com")],
+            trash: vec![
+                TrashedPassword::new(password("trashed-a.com")),
+                TrashedPassword::new(password("trashed-b.com")),
+            ],
+        }
+        .write(&mut file)
+        .unwrap();
+        let mut reader = file.reader();
+        let trashed = reader.read_trash_section_header().unwrap();
+        let mut offsets = vec![reader.offset()];
+        for _ in 0..trashed {
+            reader.read_trashed_password().unwrap();
+            offsets.push(reader.offset());
+        }
+        while !reader.eof() {
+            reader.read_password().unwrap();
+            offsets.push(reader.offset());
+        }
+        reader.seek(0);
+        (file, offsets)
+    }
+
+    fn read_content(file: &mut EncryptedFile) -> Vec<u8> {
+        let mut reader = file.reader();
+        let mut content = Vec::new();
+        while !reader.eof() {
+            let mut byte = [0u8];
+            reader.read_exact(&mut byte).unwrap();
+            content.push(byte[0]);
+        }
+        reader.seek(0);
+        content
+    }
+
+    fn write_content(file: &mut EncryptedFile, content: &[u8]) {
+        let mut writer = file.writer();
+        writer.write_all(content);
+        writer.flush().unwrap();
+    }
+
+    fn domains(salvaged: &SalvagedContent) -> (Vec<&str>, Vec<&str>) {
+        let trash = salvaged.content.trash.iter();
+        let passwords = salvaged.content.passwords.iter();
+        (
+            trash
+                .map(|trashed| trashed.password.domain.as_str())
+                .collect(),
+            passwords.map(|password| password.domain.as_str()).collect(),
+        )
+    }
+
+    #[test]
+    fn intact_content_is_salvaged_whole() {
+        let path = TempPath::unique();
+        let (mut file, _) = passwords_file(&path);
+        let salvaged = salvage_passwords_file(&mut file);
+        assert_eq!(
+            domains(&salvaged),
+            (
+                vec!["trashed-a.com", "trashed-b.com"],
+                vec!["a.com", "b.com", "c.com"]
+            )
+        );
+        assert!(salvaged.skipped.is_empty());
+    }
+
+    #[test]
+    fn garbage_between_entries_is_skipped() {
+        let path = TempPath::unique();
+        let (mut file, offsets) = passwords_file(&path);
+        let mut content = read_content(&mut file);
+        // between the trashed passwords, and between the passwords
+        let garbage = [0xffu8; 13];
+        content.splice(offsets[4]..offsets[4], garbage);
+        content.splice(offsets[1]..offsets[1], garbage);
+        write_content(&mut file, &content);
+        let salvaged = salvage_passwords_file(&mut file);
+        assert_eq!(
+            domains(&salvaged),
+            (
+                vec!["trashed-a.com", "trashed-b.com"],
+                vec!["a.com", "b.com", "c.com"]
+            )
+        );
+        let second_garbage = offsets[4] + garbage.len();
+        assert_eq!(
+            salvaged.skipped,
+            vec![
+                offsets[1]..offsets[1] + garbage.len(),
+                second_garbage..second_garbage + garbage.len()
+            ]
+        );
+    }
+
+    #[test]
+    fn corrupted_entries_are_skipped() {
+        let path = TempPath::unique();
+        let (mut file, offsets) = passwords_file(&path);
+        let mut content = read_content(&mut file);
+        // the length of the domain of the first trashed password, right after its removal time
+        content[offsets[0] + 8 + 7] = 0x7f;
+        // the last byte of the sealed fields of the second password, which fails authentication
+        content[offsets[4] - 1] ^= 1;
+        write_content(&mut file, &content);
+        let salvaged = salvage_passwords_file(&mut file);
+        assert_eq!(
+            domains(&salvaged),
+            (vec!["trashed-b.com"], vec!["a.com", "c.com"])
+        );
+        assert_eq!(
+            salvaged.skipped,
+            vec![offsets[0]..offsets[1], offsets[3]..offsets[4]]
+        );
+    }
+
+    #[test]
+    fn truncated_entry_is_skipped() {
+        let path = TempPath::unique();
+        let (mut file, offsets) = passwords_file(&path);
+        let mut content = read_content(&mut file);
+        content.truncate(offsets[4] + 20);
+        write_content(&mut file, &content);
+        let salvaged = salvage_passwords_file(&mut file);
+        assert_eq!(
+            domains(&salvaged),
+            (
+                vec!["trashed-a.com", "trashed-b.com"],
+                vec!["a.com", "b.com"]
+            )
+        );
+        assert_eq!(salvaged.skipped, vec![offsets[4]..content.len()]);
+    }
+
+    #[test]
+    fn corrupted_trash_section_header_is_skipped() {
+        let path = TempPath::unique();
+        let (mut file, _) = passwords_file(&path);
+        let mut content = read_content(&mut file);
+        // an amount of trashed passwords that the passwords can't be mistaken for
+        content[..8].copy_from_slice(&u64::MAX.to_le_bytes());
+        write_content(&mut file, &content);
+        let salvaged = salvage_passwords_file(&mut file);
+        assert_eq!(
+            domains(&salvaged),
+            (
+                vec!["trashed-a.com", "trashed-b.com"],
+                vec!["a.com", "b.com", "c.com"]
+            )
+        );
+        assert!(salvaged.skipped.is_empty());
+    }
+}
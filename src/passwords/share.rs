@@ -0,0 +1,30 @@
+use crate::locker::{write_shared_file, Identity, RecipientKey, Result};
+use crate::passwords::{Password, PasswordReader, PasswordWriter};
+use crate::secret::Secret;
+use std::path::Path;
+
+// writes the entry to a new file that only the recipient can decrypt, with its fields in the same
+// order as in entries written before entries were sealed
+pub fn share_password<P: AsRef<Path>>(
+    path: P,
+    recipient: &RecipientKey,
+    password: &Password,
+) -> Result<()> {
+    let mut content = Secret::new(Vec::new());
+    content
+        .write_string(&password.password)
+        .write_string(&password.domain)
+        .write_string(&password.username)
+        .write_additional_fields(&password.additional_fields);
+    write_shared_file(path, recipient, &content)
+}
+
+pub fn receive_password<P: AsRef<Path>>(path: P, identity: &Identity) -> Result<Password> {
+    let mut reader = identity.read_shared_file(path)?;
+    Ok(Password {
+        password: Secret::new(reader.read_string()?),
+        domain: reader.read_string()?,
+        username: reader.read_string()?,
+        additional_fields: reader.read_additional_fields()?,
+    })
+}
@@ -8,9 +8,18 @@ pub enum SortBy {
     Username,
     Other(String),
 }
+impl std::str::FromStr for SortBy {
+    type Err = std::convert::Infallible;
+    fn from_str(s: &str) -> std::result::Result<SortBy, Self::Err> {
+        Ok(match s {
+            "domain" => SortBy::Domain,
+            "username" => SortBy::Username,
+            field_name => SortBy::Other(field_name.to_string()),
+        })
+    }
+}
 
 pub struct SortedPasswords{
-    pub sort_by:SortBy,
     pub entries:HashMap<Option<String>, Vec<Password>>,
 }
 
@@ -32,7 +41,6 @@ pub trait Sort{
 impl<T: fallible_iterator::FallibleIterator<Item = Password, Error = Error>> Sort for T {
     fn sort(mut self, sort_by: SortBy) -> Result<SortedPasswords> {
         let mut result = SortedPasswords{
-            sort_by:sort_by.clone(),
             entries:HashMap::new(),
         };
         while let Some(password) = self.next()? {
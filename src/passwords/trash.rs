@@ -0,0 +1,131 @@
+use crate::locker::{
+    EncryptedFile, EncryptedFlush, EncryptedRead, EncryptedSeal, EncryptedUnseal, EncryptedWrite,
+    Result,
+};
+use crate::passwords::{Password, PasswordReader, PasswordWriter};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// the trash section is stored at the start of the content of the passwords file, so that new
+// passwords can still be appended to the end of the file. it starts with the amount of trashed
+// passwords, and is always present.
+//
+// before strings were length prefixed, the section was only present if the trash wasn't empty,
+// and started with a byte that could never start a password, since passwords started with a
+// utf-8 string
+pub const TRASH_SECTION_MARKER: u8 = 0xff;
+const ALWAYS_PRESENT_TRASH_SECTION_FORMAT_VERSION: u16 = 3;
+
+#[derive(Debug)]
+pub struct TrashedPassword {
+    pub password: Password,
+    // seconds since the unix epoch
+    pub removed_at: u64,
+}
+impl TrashedPassword {
+    pub fn new(password: Password) -> TrashedPassword {
+        TrashedPassword {
+            password,
+            removed_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|duration| duration.as_secs())
+                .unwrap_or(0),
+        }
+    }
+}
+
+pub trait TrashReader {
+    // returns the amount of trashed passwords
+    fn read_trash_section_header(&mut self) -> Result<u64>;
+    fn read_trashed_password(&mut self) -> Result<TrashedPassword>
+    where
+        Self: EncryptedUnseal;
+    fn read_trash_section(&mut self) -> Result<Vec<TrashedPassword>>
+    where
+        Self: EncryptedUnseal;
+    // moves past the trash section without decrypting the sealed fields of its entries
+    fn skip_trash_section(&mut self) -> Result<()>;
+}
+impl<T: EncryptedRead> TrashReader for T {
+    fn read_trash_section_header(&mut self) -> Result<u64> {
+        if self.format_version() < ALWAYS_PRESENT_TRASH_SECTION_FORMAT_VERSION {
+            if self.peek() != Some(TRASH_SECTION_MARKER) {
+                return Ok(0);
+            }
+            self.read_exact(&mut [0u8])?;
+        }
+        self.read_u64()
+    }
+    fn read_trashed_password(&mut self) -> Result<TrashedPassword>
+    where
+        Self: EncryptedUnseal,
+    {
+        let removed_at = self.read_u64()?;
+        Ok(TrashedPassword {
+            password: self.read_password()?,
+            removed_at,
+        })
+    }
+    fn read_trash_section(&mut self) -> Result<Vec<TrashedPassword>>
+    where
+        Self: EncryptedUnseal,
+    {
+        let amount = self.read_trash_section_header()?;
+        let mut trash = Vec::new();
+        for _ in 0..amount {
+            trash.push(self.read_trashed_password()?);
+        }
+        Ok(trash)
+    }
+    fn skip_trash_section(&mut self) -> Result<()> {
+        let amount = self.read_trash_section_header()?;
+        for _ in 0..amount {
+            self.read_u64()?;
+            self.read_password_entry()?;
+        }
+        Ok(())
+    }
+}
+
+pub trait TrashWriter {
+    fn write_trash_section(&mut self, trash: &[TrashedPassword]) -> Result<&mut Self>
+    where
+        Self: EncryptedSeal;
+}
+impl<T: EncryptedWrite> TrashWriter for T {
+    fn write_trash_section(&mut self, trash: &[TrashedPassword]) -> Result<&mut Self>
+    where
+        Self: EncryptedSeal,
+    {
+        self.write_u64(trash.len() as u64);
+        for trashed_password in trash {
+            self.write_u64(trashed_password.removed_at)
+                .write_password(&trashed_password.password)?;
+        }
+        Ok(self)
+    }
+}
+
+// the whole content of the passwords file, used by commands that need to rewrite the file
+pub struct PasswordsFileContent {
+    pub passwords: Vec<Password>,
+    pub trash: Vec<TrashedPassword>,
+}
+impl PasswordsFileContent {
+    pub fn read(file: &mut EncryptedFile) -> Result<PasswordsFileContent> {
+        let mut reader = file.reader();
+        let trash = reader.read_trash_section()?;
+        let mut passwords = Vec::new();
+        while !reader.eof() {
+            passwords.push(reader.read_password()?);
+        }
+        Ok(PasswordsFileContent { passwords, trash })
+    }
+    // rewrites the whole file, the writer encrypts it with a fresh nonce
+    pub fn write(&self, file: &mut EncryptedFile) -> Result<()> {
+        let mut writer = file.writer();
+        writer
+            .write_trash_section(&self.trash)?
+            .write_passwords(&self.passwords)?;
+        writer.flush()
+    }
+}
@@ -0,0 +1,146 @@
+use std::alloc::{alloc_zeroed, dealloc, handle_alloc_error, Layout};
+use std::fmt;
+use std::ops::Deref;
+use std::ptr::NonNull;
+use zeroize::Zeroize;
+
+const REDACTED: &str = "[redacted]";
+
+// a value that must not leak, such as a password or a key. it's wiped from memory when it's
+// dropped, and is never printed, so it must be exposed explicitly to print it
+pub struct Secret<T: Zeroize>(T);
+impl<T: Zeroize> Secret<T> {
+    pub fn new(value: T) -> Secret<T> {
+        Secret(value)
+    }
+    pub fn expose(&self) -> &T {
+        &self.0
+    }
+    pub fn expose_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+impl Secret<Vec<u8>> {
+    // appends the bytes, moving the content to a larger allocation and wiping the old one when it
+    // must grow, so that growing it doesn't leave copies of the content behind in freed memory
+    pub fn extend_from_slice(&mut self, bytes: &[u8]) {
+        let vec = &mut self.0;
+        if vec.capacity() - vec.len() < bytes.len() {
+            let mut grown = Vec::with_capacity((vec.len() + bytes.len()).max(vec.capacity() * 2));
+            grown.extend_from_slice(vec);
+            std::mem::replace(vec, grown).zeroize();
+        }
+        vec.extend_from_slice(bytes);
+    }
+    // moves the content out, leaving the secret empty
+    pub fn take(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.0)
+    }
+}
+impl<T: Zeroize> Deref for Secret<T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+impl<T: Zeroize + AsRef<[u8]>> AsRef<[u8]> for Secret<T> {
+    fn as_ref(&self) -> &[u8] {
+        self.0.as_ref()
+    }
+}
+impl<T: Zeroize + PartialEq> PartialEq for Secret<T> {
+    fn eq(&self, other: &Secret<T>) -> bool {
+        self.0 == other.0
+    }
+}
+impl<T: Zeroize> fmt::Debug for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+impl<T: Zeroize> fmt::Display for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+impl<T: Zeroize> Drop for Secret<T> {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+fn page_size() -> usize {
+    match unsafe { libc::sysconf(libc::_SC_PAGESIZE) } {
+        size if size > 0 => size as usize,
+        _ => 4096,
+    }
+}
+
+// an immutable buffer for decrypted content, which is locked in memory so that it can't be
+// swapped out to disk, and is wiped when it's dropped. the buffer gets whole pages of its own,
+// since unlocking it when it's dropped unlocks the pages it's on
+pub struct SecretBuffer {
+    ptr: NonNull<u8>,
+    len: usize,
+    // None if the buffer is empty, in which case nothing is allocated
+    layout: Option<Layout>,
+    is_locked: bool,
+}
+impl SecretBuffer {
+    // the given bytes are copied into the buffer and wiped
+    pub fn new(mut bytes: Vec<u8>) -> SecretBuffer {
+        let mut buffer = SecretBuffer {
+            ptr: NonNull::dangling(),
+            len: bytes.len(),
+            layout: None,
+            is_locked: false,
+        };
+        if !bytes.is_empty() {
+            let page_size = page_size();
+            let size = bytes.len().div_ceil(page_size) * page_size;
+            let layout = match Layout::from_size_align(size, page_size) {
+                Ok(layout) => layout,
+                Err(_) => handle_alloc_error(Layout::new::<u8>()),
+            };
+            buffer.ptr = match NonNull::new(unsafe { alloc_zeroed(layout) }) {
+                Some(ptr) => ptr,
+                None => handle_alloc_error(layout),
+            };
+            buffer.layout = Some(layout);
+            unsafe {
+                std::ptr::copy_nonoverlapping(bytes.as_ptr(), buffer.ptr.as_ptr(), bytes.len());
+            }
+            // locking is best effort, it fails if the user's limit of locked memory is reached
+            buffer.is_locked =
+                unsafe { libc::mlock(buffer.ptr.as_ptr() as *const libc::c_void, size) } == 0;
+        }
+        bytes.zeroize();
+        buffer
+    }
+}
+impl Deref for SecretBuffer {
+    type Target = [u8];
+    fn deref(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+impl fmt::Debug for SecretBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+impl Drop for SecretBuffer {
+    fn drop(&mut self) {
+        let layout = match self.layout {
+            Some(layout) => layout,
+            None => return,
+        };
+        unsafe {
+            std::slice::from_raw_parts_mut(self.ptr.as_ptr(), layout.size()).zeroize();
+            if self.is_locked {
+                libc::munlock(self.ptr.as_ptr() as *const libc::c_void, layout.size());
+            }
+            dealloc(self.ptr.as_ptr(), layout);
+        }
+    }
+}
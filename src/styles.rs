@@ -1,7 +1,5 @@
 use ansi_term::{Color,Style};
 
-use crate::passwords::SortBy;
-
 pub fn error_style()->Style{
     Color::Red.bold()
 }
@@ -0,0 +1,22 @@
+use rand::{thread_rng, RngCore};
+use std::path::{Path, PathBuf};
+
+// a path in the temporary directory that no other test uses, whose file is removed once it's
+// dropped
+pub struct TempPath(PathBuf);
+impl TempPath {
+    pub fn unique() -> TempPath {
+        let file_name = format!("pwm-test-{:016x}", thread_rng().next_u64());
+        TempPath(std::env::temp_dir().join(file_name))
+    }
+}
+impl AsRef<Path> for TempPath {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+impl Drop for TempPath {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
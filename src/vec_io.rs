@@ -1,11 +1,14 @@
+use crate::secret::SecretBuffer;
+
+// the buffer holds the decrypted content of files, so it's a secret buffer
 pub struct VecReader{
-    vector:Vec<u8>,
+    vector:SecretBuffer,
     pos:usize,
 }
 impl VecReader{
     pub fn new(vec:Vec<u8>)->VecReader{
         VecReader{
-            vector:vec,
+            vector:SecretBuffer::new(vec),
             pos:0,
         }
     }
@@ -21,30 +24,17 @@ impl VecReader{
     pub fn consume(&mut self,amount:usize){
         self.pos+=amount
     }
-    pub fn seek_back(&mut self,amount:usize){
-        if amount>self.pos{
-            self.pos=0;
-        }else{
-            self.pos-=amount;
-        }
-    }
-    pub fn rest(&self)->&[u8]{
-        &self.vector[self.pos..]
+    pub fn position(&self)->usize{
+        self.pos
     }
-    pub fn rest_mut(&mut self)->&mut [u8]{
-        &mut self.vector[self.pos..]
+    pub fn set_position(&mut self,pos:usize){
+        self.pos=pos.min(self.vector.len())
     }
     pub fn buffer(&self)->&[u8]{
         &self.vector
     }
-    pub fn inner_vec(self)->Vec<u8>{
-        self.vector
-    }
-    pub fn inner_vec_ref(&self)->&Vec<u8>{
-        &self.vector
-    }
-    pub fn inner_vec_mut(&mut self)->&mut Vec<u8>{
-        &mut self.vector
+    pub fn rest(&self)->&[u8]{
+        &self.vector[self.pos..]
     }
     pub fn eof(&self)->bool{
         self.pos>=self.vector.len()
@@ -1,11 +1,10 @@
-use crate::locker::{io_to_locker_error, to_locker_error, ErrorKind, Result};
-use clipboard::{x11_clipboard::X11ClipboardContext, ClipboardProvider};
+use crate::locker::{io_to_locker_error, ErrorKind, Result};
 use std::io::Write;
 use std::process::{Command, Stdio};
 pub fn clipboard_set(content: &str) -> Result<()> {
     let mut child = io_to_locker_error(
         Command::new("xclip")
-            .args(&["-in", "-selection", "clipboard"])
+            .args(["-in", "-selection", "clipboard"])
             .stdin(Stdio::piped())
             .spawn(),
         ErrorKind::CopyToClipboard,
@@ -16,7 +15,7 @@ pub fn clipboard_set(content: &str) -> Result<()> {
         None => return Err(ErrorKind::CopyToClipboard.without_source_error()),
     };
 
-    match stdin.write(content.as_bytes()) {
+    match stdin.write_all(content.as_bytes()) {
         Ok(_)=>Ok(()),
         Err(e)=>Err(ErrorKind::CopyToClipboard.with_source_error(e)),
     }